@@ -0,0 +1,73 @@
+use std::ops::AddAssign;
+use std::time::Duration;
+
+use ipnet::IpNet;
+
+/// Statistics of the prefixes of one IP family added to a `ReduceTrie`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FamilyStats {
+    /// Number of prefixes added
+    pub inputs: usize,
+    /// Prefixes equal to one added before in the same input (or left in the trie), whether
+    /// covered by a less specific prefix or not
+    pub duplicates: usize,
+    /// Prefixes removed since they are covered by a less specific prefix
+    pub covered: usize,
+    /// Time spent inserting the prefixes into the trie. Readers insert both families while
+    /// reading, so for them this is the time spent reading the whole input.
+    pub build_time: Duration,
+    /// Time spent removing the hosts covered by prefixes of the trie
+    pub coverage_time: Duration,
+}
+
+impl AddAssign for FamilyStats {
+    fn add_assign(&mut self, other: Self) {
+        self.inputs += other.inputs;
+        self.duplicates += other.duplicates;
+        self.covered += other.covered;
+        self.build_time += other.build_time;
+        self.coverage_time += other.coverage_time;
+    }
+}
+
+/// Statistics of the prefixes added to a `ReduceTrie` in bulk (`from_reader`, `from_prefixes`,
+/// `extend` and `collect`), per IP family. Prefixes added with `insert` are not counted.
+/// `union` and `intersect` add the statistics of both tries, counting the prefixes left in both
+/// as duplicates and the ones covered by a prefix of the other trie as covered; for `intersect`,
+/// prefixes outside the intersection are counted as covered.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::ReduceTrie;
+///
+/// let input = "10.0.0.0/8\n10.1.0.0/16\n10.1.0.0/16\n192.168.1.1\n192.168.1.1\n";
+/// let (trie, _) = ReduceTrie::from_reader(input.as_bytes()).unwrap();
+///
+/// let stats = trie.stats().ipv4;
+/// assert_eq!(stats.inputs, 5);
+/// assert_eq!(stats.duplicates, 2);
+/// assert_eq!(stats.covered, 1);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BuildStats {
+    pub ipv4: FamilyStats,
+    pub ipv6: FamilyStats,
+}
+
+impl BuildStats {
+    /// Returns the statistics of the family of the prefix.
+    pub(crate) fn family_mut(&mut self, prefix: &IpNet) -> &mut FamilyStats {
+        match prefix {
+            IpNet::V4(_) => &mut self.ipv4,
+            IpNet::V6(_) => &mut self.ipv6,
+        }
+    }
+}
+
+impl AddAssign for BuildStats {
+    fn add_assign(&mut self, other: Self) {
+        self.ipv4 += other.ipv4;
+        self.ipv6 += other.ipv6;
+    }
+}
@@ -1,6 +1,84 @@
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
-use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use ipnet::{IpNet, IpSubnets, Ipv4Net, Ipv4Subnets, Ipv6Net, Ipv6Subnets};
+
+use crate::extract::{self, Extract};
+use crate::ipv4_embedding::Ipv4Embedding;
+
+/// Reason why a string could not be parsed into a prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// The string (or the part in front of the `/`) is not a valid IPv4 or IPv6 address.
+    InvalidAddress,
+    /// The part after the `/` is not a number.
+    InvalidPrefixLength,
+    /// The prefix length exceeds the length of the address (32 for IPv4, 128 for IPv6).
+    PrefixLengthOutOfRange { len: u32, max: u8 },
+    /// The addresses of a range (`start-end`) are of different families or the start address is
+    /// greater than the end address.
+    InvalidRange,
+    /// The address has bits set beyond the prefix length (e.g. "10.1.2.3/8"), only reported with
+    /// `Normalization::Reject`.
+    HostBitsSet,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidAddress => write!(f, "invalid IP address"),
+            ParseError::InvalidPrefixLength => write!(f, "invalid prefix length"),
+            ParseError::PrefixLengthOutOfRange { len, max } => {
+                write!(f, "prefix length {len} out of range (max {max})")
+            }
+            ParseError::InvalidRange => write!(f, "invalid address range"),
+            ParseError::HostBitsSet => write!(f, "host bits set"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Handling of prefixes with host bits set (e.g. "10.1.2.3/8").
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Normalization {
+    /// Keep the prefix as written
+    #[default]
+    Keep,
+    /// Clear the host bits (e.g. "10.1.2.3/8" becomes "10.0.0.0/8")
+    Truncate,
+    /// Reject the prefix as invalid
+    Reject,
+}
+
+impl std::str::FromStr for Normalization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep" => Ok(Normalization::Keep),
+            "truncate" => Ok(Normalization::Truncate),
+            "reject" => Ok(Normalization::Reject),
+            _ => Err(format!("Unknown normalization: {s}")),
+        }
+    }
+}
+
+/// Options controlling how lines of input are parsed into prefixes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub normalization: Normalization,
+    /// IPv6 prefixes embedding IPv4 addresses to convert into their IPv4 family
+    pub ipv4_embedding: Ipv4Embedding,
+    /// Extract the addresses and prefixes found anywhere in the line instead of parsing the whole
+    /// line. Lines without any address are skipped, comments are not recognized. Lines with a
+    /// selected prefix rejected by the normalization are invalid.
+    pub extract: Option<Extract>,
+    /// Accept multiple prefixes or ranges per line, separated by commas or whitespace (ranges
+    /// must not contain whitespace then, e.g. `192.0.2.10-192.0.2.200`)
+    pub multiple: bool,
+}
 
 /// Parses a string into an `IpNet`.
 ///
@@ -13,11 +91,13 @@ use ipnet::{IpNet, Ipv4Net, Ipv6Net};
 ///
 /// The string is trimmed of leading and trailing whitespace before parsing.
 ///
-pub fn from_str(s: &str) -> Option<IpNet> {
+/// If the string can not be parsed, the returned `ParseError` describes the reason.
+///
+pub fn from_str(s: &str) -> Result<IpNet, ParseError> {
     let s = s.trim();
 
     if let Ok(ip) = s.parse::<IpNet>() {
-        return Some(ip);
+        return Ok(ip);
     }
 
     if s.contains(":")
@@ -29,20 +109,202 @@ pub fn from_str(s: &str) -> Option<IpNet> {
         return ipv4_to_ipnet(ip);
     }
 
-    None
+    Err(classify_error(s))
+}
+
+/// Parses a string into an `IpNet` like `from_str`, handling host bits set according to the
+/// given normalization.
+pub fn from_str_normalized(s: &str, normalization: Normalization) -> Result<IpNet, ParseError> {
+    normalize(from_str(s)?, normalization)
+}
+
+fn normalize(prefix: IpNet, normalization: Normalization) -> Result<IpNet, ParseError> {
+    match normalization {
+        Normalization::Keep => Ok(prefix),
+        Normalization::Truncate => Ok(prefix.trunc()),
+        Normalization::Reject if prefix.addr() != prefix.network() => Err(ParseError::HostBitsSet),
+        Normalization::Reject => Ok(prefix),
+    }
+}
+
+/// The prefixes of a single line of input, either a single prefix or the minimal set of prefixes
+/// covering an address range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Prefixes {
+    Single(Option<IpNet>),
+    Range(IpSubnets, Ipv4Embedding),
+    Extracted(VecDeque<IpNet>),
+}
+
+impl Iterator for Prefixes {
+    type Item = IpNet;
+
+    fn next(&mut self) -> Option<IpNet> {
+        match self {
+            Prefixes::Single(prefix) => prefix.take(),
+            Prefixes::Range(subnets, embedding) => {
+                subnets.next().map(|prefix| embedding.canonicalize(prefix))
+            }
+            Prefixes::Extracted(prefixes) => prefixes.pop_front(),
+        }
+    }
+}
+
+/// Parses a string into the prefixes it describes. Besides the formats supported by `from_str`
+/// address ranges (e.g. "192.0.2.10-192.0.2.200" or "2001:db8::1 - 2001:db8::ff") are accepted,
+/// which are decomposed into the minimal set of prefixes covering the range.
+pub fn parse_prefixes(s: &str, options: ParseOptions) -> Result<Prefixes, ParseError> {
+    match s.split_once('-') {
+        Some((start, end)) => {
+            parse_range(start, end).map(|subnets| Prefixes::Range(subnets, options.ipv4_embedding))
+        }
+        None => from_str_normalized(s, options.normalization)
+            .map(|prefix| Prefixes::Single(Some(options.ipv4_embedding.canonicalize(prefix)))),
+    }
+}
+
+fn parse_range(start: &str, end: &str) -> Result<IpSubnets, ParseError> {
+    let parse_addr = |s: &str| {
+        s.trim()
+            .parse::<IpAddr>()
+            .map_err(|_| ParseError::InvalidAddress)
+    };
+
+    match (parse_addr(start)?, parse_addr(end)?) {
+        (IpAddr::V4(start), IpAddr::V4(end)) if start <= end => {
+            Ok(IpSubnets::V4(Ipv4Subnets::new(start, end, 0)))
+        }
+        (IpAddr::V6(start), IpAddr::V6(end)) if start <= end => {
+            Ok(IpSubnets::V6(Ipv6Subnets::new(start, end, 0)))
+        }
+        _ => Err(ParseError::InvalidRange),
+    }
+}
+
+/// Markers starting a comment, which reaches until the end of the line.
+const COMMENT_MARKERS: [&str; 3] = ["#", ";", "//"];
+
+/// Parses a line of input into its prefixes, see `parse_prefixes`.
+///
+/// Returns `None` for lines without any content (e.g. blank lines or comments), which are
+/// skipped instead of being reported as invalid.
+pub fn parse_line(line: &str, options: ParseOptions) -> Option<Result<Prefixes, ParseError>> {
+    parse_annotated_line(line, options).map(|result| result.map(|(prefix, _)| prefix))
+}
+
+/// Parses a line of input like `parse_line`, additionally returning the text of a trailing
+/// comment (e.g. `RFC1918` for `10.0.0.0/8 # RFC1918`) as annotation of the prefix.
+pub fn parse_annotated_line(
+    line: &str,
+    options: ParseOptions,
+) -> Option<Result<(Prefixes, Option<&str>), ParseError>> {
+    if let Some(extract) = options.extract {
+        return extract_line(line, extract, options).map(|r| r.map(|prefixes| (prefixes, None)));
+    }
+
+    let (content, comment) = split_comment(line);
+    if content.trim().is_empty() {
+        return None;
+    }
+
+    let annotation = comment.map(str::trim).filter(|c| !c.is_empty());
+    let prefixes = match options.multiple {
+        true => parse_list(content, options),
+        false => parse_prefixes(content, options),
+    };
+
+    Some(prefixes.map(|prefixes| (prefixes, annotation)))
+}
+
+/// Parses a list of prefixes separated by commas or whitespace, fails on the first invalid one.
+fn parse_list(s: &str, options: ParseOptions) -> Result<Prefixes, ParseError> {
+    let mut prefixes = VecDeque::new();
+
+    for item in s.split([',', ' ', '\t']).filter(|item| !item.is_empty()) {
+        prefixes.extend(parse_prefixes(item, options)?);
+    }
+
+    Ok(Prefixes::Extracted(prefixes))
+}
+
+/// Extracts the selected prefixes of the line, returns `None` if there are none. Fails if a
+/// selected prefix is rejected by the normalization.
+fn extract_line(
+    line: &str,
+    extract: Extract,
+    options: ParseOptions,
+) -> Option<Result<Prefixes, ParseError>> {
+    let mut found = extract::extract_prefixes(line);
+
+    let selected: Vec<IpNet> = match extract {
+        Extract::All => found.collect(),
+        Extract::Nth(n) => found.nth(n.checked_sub(1)?).into_iter().collect(),
+    };
+    if selected.is_empty() {
+        return None;
+    }
+
+    let prefixes = selected
+        .into_iter()
+        .map(|prefix| {
+            normalize(prefix, options.normalization)
+                .map(|prefix| options.ipv4_embedding.canonicalize(prefix))
+        })
+        .collect::<Result<VecDeque<_>, _>>();
+
+    Some(prefixes.map(Prefixes::Extracted))
+}
+
+/// Splits the line at the first comment marker into content and comment text.
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let marker = COMMENT_MARKERS
+        .iter()
+        .filter_map(|marker| line.find(marker).map(|pos| (pos, marker.len())))
+        .min();
+
+    match marker {
+        Some((pos, len)) => (&line[..pos], Some(&line[pos + len..])),
+        None => (line, None),
+    }
 }
 
-fn ipv4_to_ipnet(ip: Ipv4Addr) -> Option<IpNet> {
+fn classify_error(s: &str) -> ParseError {
+    let Some((addr, len)) = s.split_once('/') else {
+        return ParseError::InvalidAddress;
+    };
+
+    let Ok(addr) = addr.parse::<IpAddr>() else {
+        return ParseError::InvalidAddress;
+    };
+
+    let Ok(len) = len.parse::<u32>() else {
+        return ParseError::InvalidPrefixLength;
+    };
+
+    let max = match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+
+    if len <= u32::from(max) {
+        // e.g. a leading + sign, which is accepted by u32 but not by IpNet
+        return ParseError::InvalidPrefixLength;
+    }
+
+    ParseError::PrefixLengthOutOfRange { len, max }
+}
+
+fn ipv4_to_ipnet(ip: Ipv4Addr) -> Result<IpNet, ParseError> {
     match Ipv4Net::new(ip, 32) {
-        Ok(net) => Some(IpNet::V4(net)),
-        Err(_) => None,
+        Ok(net) => Ok(IpNet::V4(net)),
+        Err(_) => Err(ParseError::InvalidAddress),
     }
 }
 
-fn ipv6_to_ipnet(ip: Ipv6Addr) -> Option<IpNet> {
+fn ipv6_to_ipnet(ip: Ipv6Addr) -> Result<IpNet, ParseError> {
     match Ipv6Net::new(ip, 128) {
-        Ok(net) => Some(IpNet::V6(net)),
-        Err(_) => None,
+        Ok(net) => Ok(IpNet::V6(net)),
+        Err(_) => Err(ParseError::InvalidAddress),
     }
 }
 
@@ -52,35 +314,323 @@ mod tests {
     use ipnet::{IpNet, Ipv4Net, Ipv6Net};
     use std::net::{Ipv4Addr, Ipv6Addr};
 
+    fn single(s: &str) -> Prefixes {
+        Prefixes::Single(Some(s.parse().unwrap()))
+    }
+
     #[test]
     fn test_parse_to_cidr_with_cidr_notation() {
         let net: Ipv4Net = "192.168.0.0/24".parse().unwrap();
-        assert_eq!(from_str(" 192.168.0.0/24 "), Some(IpNet::V4(net)));
+        assert_eq!(from_str(" 192.168.0.0/24 "), Ok(IpNet::V4(net)));
     }
 
     #[test]
     fn test_parse_to_cidr_with_single_ipv4_address() {
         let ip = Ipv4Addr::new(10, 0, 0, 1);
         let net = Ipv4Net::new(ip, 32).unwrap();
-        assert_eq!(from_str("10.0.0.1"), Some(IpNet::V4(net)));
+        assert_eq!(from_str("10.0.0.1"), Ok(IpNet::V4(net)));
     }
 
     #[test]
     fn test_parse_to_cidr_with_single_ipv6_address() {
         let ip = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
         let net = Ipv6Net::new(ip, 128).unwrap();
-        assert_eq!(from_str("2001:db8::1"), Some(IpNet::V6(net)));
+        assert_eq!(from_str("2001:db8::1"), Ok(IpNet::V6(net)));
     }
 
     #[test]
     fn test_parse_to_cidr_with_invalid_input() {
-        assert_eq!(from_str("not an ip"), None);
+        assert_eq!(from_str("not an ip"), Err(ParseError::InvalidAddress));
     }
 
     #[test]
     fn test_parse_to_cidr_with_whitespace() {
         let ip = Ipv4Addr::new(10, 0, 0, 1);
         let net = Ipv4Net::new(ip, 32).unwrap();
-        assert_eq!(from_str("  10.0.0.1  "), Some(IpNet::V4(net)));
+        assert_eq!(from_str("  10.0.0.1  "), Ok(IpNet::V4(net)));
+    }
+
+    #[test]
+    fn test_parse_line_skips_blank_lines() {
+        assert_eq!(parse_line("", ParseOptions::default()), None);
+        assert_eq!(parse_line(" \t ", ParseOptions::default()), None);
+        assert_eq!(
+            parse_line("x", ParseOptions::default()),
+            Some(Err(ParseError::InvalidAddress))
+        );
+    }
+
+    #[test]
+    fn test_parse_line_skips_comments() {
+        assert_eq!(parse_line("# 10.0.0.0/8", ParseOptions::default()), None);
+        assert_eq!(parse_line("  ; comment", ParseOptions::default()), None);
+        assert_eq!(parse_line("// comment", ParseOptions::default()), None);
+        assert_eq!(
+            parse_line("10.0.0.0/8 # RFC1918", ParseOptions::default()),
+            Some(Ok(single("10.0.0.0/8")))
+        );
+        assert_eq!(
+            parse_line("x # comment", ParseOptions::default()),
+            Some(Err(ParseError::InvalidAddress))
+        );
+    }
+
+    #[test]
+    fn test_parse_annotated_line() {
+        let net = single("10.0.0.0/8");
+        assert_eq!(
+            parse_annotated_line("10.0.0.0/8 # RFC1918", ParseOptions::default()),
+            Some(Ok((net.clone(), Some("RFC1918"))))
+        );
+        assert_eq!(
+            parse_annotated_line("10.0.0.0/8;private // internal", ParseOptions::default()),
+            Some(Ok((net.clone(), Some("private // internal"))))
+        );
+        assert_eq!(
+            parse_annotated_line("2001:db8::/32 // documentation", ParseOptions::default()),
+            Some(Ok((single("2001:db8::/32"), Some("documentation"))))
+        );
+        assert_eq!(
+            parse_annotated_line("10.0.0.0/8 #", ParseOptions::default()),
+            Some(Ok((net.clone(), None)))
+        );
+        assert_eq!(
+            parse_annotated_line("10.0.0.0/8", ParseOptions::default()),
+            Some(Ok((net, None)))
+        );
+    }
+
+    #[test]
+    fn test_normalization() {
+        let net = |s: &str| s.parse::<IpNet>().unwrap();
+
+        assert_eq!(
+            from_str_normalized("10.1.2.3/8", Normalization::Keep),
+            Ok(net("10.1.2.3/8"))
+        );
+        assert_eq!(
+            from_str_normalized("10.1.2.3/8", Normalization::Truncate),
+            Ok(net("10.0.0.0/8"))
+        );
+        assert_eq!(
+            from_str_normalized("2001:db8::1/32", Normalization::Truncate),
+            Ok(net("2001:db8::/32"))
+        );
+        assert_eq!(
+            from_str_normalized("10.1.2.3/8", Normalization::Reject),
+            Err(ParseError::HostBitsSet)
+        );
+        assert_eq!(
+            from_str_normalized("10.0.0.0/8", Normalization::Reject),
+            Ok(net("10.0.0.0/8"))
+        );
+        assert_eq!(
+            from_str_normalized("10.1.2.3", Normalization::Reject),
+            Ok(net("10.1.2.3/32"))
+        );
+    }
+
+    #[test]
+    fn test_normalization_from_str() {
+        use std::str::FromStr;
+
+        assert_eq!(Normalization::from_str("keep"), Ok(Normalization::Keep));
+        assert_eq!(
+            Normalization::from_str("Truncate"),
+            Ok(Normalization::Truncate)
+        );
+        assert_eq!(Normalization::from_str("reject"), Ok(Normalization::Reject));
+        assert_eq!(
+            Normalization::from_str("fix"),
+            Err("Unknown normalization: fix".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_ipv4_range() {
+        let prefixes: Vec<String> =
+            parse_prefixes("192.0.2.10-192.0.2.20", ParseOptions::default())
+                .unwrap()
+                .map(|p| p.to_string())
+                .collect();
+
+        assert_eq!(
+            vec![
+                "192.0.2.10/31",
+                "192.0.2.12/30",
+                "192.0.2.16/30",
+                "192.0.2.20/32"
+            ],
+            prefixes
+        );
+    }
+
+    #[test]
+    fn test_parse_ipv6_range() {
+        let prefixes: Vec<String> =
+            parse_prefixes("2001:db8::1 - 2001:db8::ff", ParseOptions::default())
+                .unwrap()
+                .map(|p| p.to_string())
+                .collect();
+
+        assert_eq!(
+            vec![
+                "2001:db8::1/128",
+                "2001:db8::2/127",
+                "2001:db8::4/126",
+                "2001:db8::8/125",
+                "2001:db8::10/124",
+                "2001:db8::20/123",
+                "2001:db8::40/122",
+                "2001:db8::80/121"
+            ],
+            prefixes
+        );
+    }
+
+    #[test]
+    fn test_parse_range_covering_everything() {
+        let prefixes: Vec<String> =
+            parse_prefixes("0.0.0.0-255.255.255.255", ParseOptions::default())
+                .unwrap()
+                .map(|p| p.to_string())
+                .collect();
+
+        assert_eq!(vec!["0.0.0.0/0"], prefixes);
+        assert_eq!(
+            parse_prefixes("192.0.2.1-192.0.2.1", ParseOptions::default())
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec!["192.0.2.1/32".parse::<IpNet>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_parse_ipv4_embedding() {
+        let options = ParseOptions {
+            ipv4_embedding: Ipv4Embedding::Mapped,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            parse_prefixes("::ffff:192.0.2.1", options),
+            Ok(single("192.0.2.1/32"))
+        );
+
+        let prefixes: Vec<String> = parse_prefixes("::ffff:192.0.2.0-::ffff:192.0.2.255", options)
+            .unwrap()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(vec!["192.0.2.0/24"], prefixes);
+    }
+
+    #[test]
+    fn test_parse_line_extract() {
+        let line = "Failed password for root from 192.0.2.1 port 22, retry from 10.1.2.3/8 # 1";
+        let extracted = |extract, normalization| {
+            let options = ParseOptions {
+                normalization,
+                extract: Some(extract),
+                ..Default::default()
+            };
+
+            parse_line(line, options)
+                .map(|r| r.map(|prefixes| prefixes.map(|p| p.to_string()).collect::<Vec<_>>()))
+        };
+
+        assert_eq!(
+            extracted(Extract::All, Normalization::Keep),
+            Some(Ok(vec![
+                "192.0.2.1/32".to_string(),
+                "10.1.2.3/8".to_string()
+            ]))
+        );
+        assert_eq!(
+            extracted(Extract::Nth(2), Normalization::Truncate),
+            Some(Ok(vec!["10.0.0.0/8".to_string()]))
+        );
+        assert_eq!(
+            extracted(Extract::All, Normalization::Reject),
+            Some(Err(ParseError::HostBitsSet))
+        );
+        assert_eq!(
+            extracted(Extract::Nth(2), Normalization::Reject),
+            Some(Err(ParseError::HostBitsSet))
+        );
+        assert_eq!(
+            extracted(Extract::Nth(1), Normalization::Reject),
+            Some(Ok(vec!["192.0.2.1/32".to_string()]))
+        );
+        assert_eq!(extracted(Extract::Nth(3), Normalization::Keep), None);
+        assert_eq!(extracted(Extract::Nth(0), Normalization::Keep), None);
+    }
+
+    #[test]
+    fn test_parse_line_multiple() {
+        let options = ParseOptions {
+            multiple: true,
+            ..Default::default()
+        };
+        let parsed = |line| {
+            parse_line(line, options)
+                .map(|r| r.map(|p| p.map(|p| p.to_string()).collect::<Vec<_>>()))
+        };
+
+        assert_eq!(
+            parsed("10.0.0.0/8, 192.0.2.0-192.0.2.1\t2001:db8::1 # blocked"),
+            Some(Ok(vec![
+                "10.0.0.0/8".to_string(),
+                "192.0.2.0/31".to_string(),
+                "2001:db8::1/128".to_string()
+            ]))
+        );
+        assert_eq!(
+            parsed("10.0.0.0/8, invalid"),
+            Some(Err(ParseError::InvalidAddress))
+        );
+        assert_eq!(parsed(" , "), Some(Ok(vec![])));
+        assert_eq!(parsed("# comment"), None);
+    }
+
+    #[test]
+    fn test_parse_invalid_range() {
+        assert_eq!(
+            parse_prefixes("192.0.2.20-192.0.2.10", ParseOptions::default()),
+            Err(ParseError::InvalidRange)
+        );
+        assert_eq!(
+            parse_prefixes("192.0.2.1-2001:db8::1", ParseOptions::default()),
+            Err(ParseError::InvalidRange)
+        );
+        assert_eq!(
+            parse_prefixes("192.0.2.1-x", ParseOptions::default()),
+            Err(ParseError::InvalidAddress)
+        );
+        assert_eq!(
+            parse_prefixes("192.0.2.0/24-192.0.3.0/24", ParseOptions::default()),
+            Err(ParseError::InvalidAddress)
+        );
+    }
+
+    #[test]
+    fn test_parse_to_cidr_with_invalid_address_in_cidr() {
+        assert_eq!(from_str("192,45.3.0/24"), Err(ParseError::InvalidAddress));
+    }
+
+    #[test]
+    fn test_parse_to_cidr_with_invalid_prefix_length() {
+        assert_eq!(from_str("10.0.0.0/x"), Err(ParseError::InvalidPrefixLength));
+    }
+
+    #[test]
+    fn test_parse_to_cidr_with_prefix_length_out_of_range() {
+        assert_eq!(
+            from_str("10.0.0.0/33"),
+            Err(ParseError::PrefixLengthOutOfRange { len: 33, max: 32 })
+        );
+        assert_eq!(
+            from_str("2001:db8::/129"),
+            Err(ParseError::PrefixLengthOutOfRange { len: 129, max: 128 })
+        );
     }
 }
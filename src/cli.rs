@@ -1,6 +1,19 @@
-use clap::Parser;
+use std::fmt;
+use std::str::FromStr;
 
+use clap::{Parser, Subcommand};
+
+use net_reduce::{Extract, Ipv4Embedding, Normalization, ParseOptions, SortOrder};
+use regex::Regex;
+
+use crate::firewall::FirewallNames;
+use crate::input::Column;
+use crate::input_format::InputFormat;
 use crate::output_format::OutputFormat;
+use crate::router::RouterOptions;
+use crate::selector::Selector;
+use crate::stats_format::StatsFormat;
+use crate::template::Template;
 
 #[derive(Parser)]
 #[command(
@@ -13,11 +26,235 @@ use crate::output_format::OutputFormat;
 ///
 /// This struct defines the available command-line arguments and options
 pub struct Args {
-    /// File to read from, if not specified stdin is used
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// File to read from (can be repeated), if no file or path is specified stdin is used
     #[arg(short, long, value_name = "FILE")]
-    pub file: Option<String>,
+    pub file: Vec<String>,
+
+    /// Files to read from, `-` reads from stdin (only once). Wildcards (`*`, `?`, `[...]`, `**`) are expanded. Use --file for files named like a subcommand (diff, intersect, union)
+    #[arg(value_name = "PATH")]
+    pub paths: Vec<String>,
+
+    /// Input format, can be list, csv, tsv, json, yaml, ipset, nftables or iptables
+    #[arg(
+        short,
+        long,
+        value_name = "FORMAT",
+        default_value = "list",
+        global = true
+    )]
+    pub input_format: InputFormat,
+
+    /// Column of CSV/TSV input containing the prefixes, either its name in the header row or its index starting at 1
+    #[arg(short, long, value_name = "NAME|INDEX", global = true)]
+    pub column: Option<Column>,
 
-    /// Output format, can be json, yaml or list
-    #[arg(short, long, value_name = "FORMAT", default_value = "list")]
+    /// Location of the prefixes in JSON/YAML input (e.g. `$.prefixes[*].ip_prefix`), defaults to the elements of the root array or of its `prefixes` array
+    #[arg(long, value_name = "SELECTOR", global = true)]
+    pub select: Option<Selector>,
+
+    /// Output format, can be json, yaml, list, nftables, ipset, iptables, ip6tables, pf, cisco, juniper, bird or frr
+    #[arg(
+        short,
+        long,
+        value_name = "FORMAT",
+        default_value = "list",
+        global = true
+    )]
     pub output_format: OutputFormat,
+
+    /// Template file for the output, with the sections [header], [prefix] and [footer] and placeholders like {prefix} or {netmask} (overrides --output-format)
+    #[arg(long, value_name = "FILE", value_parser = Template::from_file, global = true)]
+    pub template: Option<Template>,
+
+    /// Name of the nftables table containing the sets
+    #[arg(long, value_name = "NAME", default_value = "filter", global = true)]
+    pub table_name: String,
+
+    /// Name of the nftables/ipset sets (suffixed with _v4/_v6), the iptables chain or the pf table
+    #[arg(long, value_name = "NAME", default_value = "blocklist", global = true)]
+    pub set_name: String,
+
+    /// Name of the router prefix lists (suffixed with _v4/_v6)
+    #[arg(long, value_name = "NAME", default_value = "net_reduce", global = true)]
+    pub prefix_list_name: String,
+
+    /// Increment of the sequence numbers of Cisco/FRR prefix lists
+    #[arg(long, value_name = "N", default_value = "5", value_parser = at_least_one::<u32>, global = true)]
+    pub seq_step: u32,
+
+    /// Sort order of the output, can be address, prefix-len, input-order or none
+    #[arg(
+        short,
+        long,
+        value_name = "ORDER",
+        default_value = "address",
+        global = true
+    )]
+    pub sort: SortOrder,
+
+    /// File with prefixes to remove from the result, covering prefixes are split if needed (always a list, --input-format, --extract and --extract-regex only apply to the input)
+    #[arg(short = 'x', long, value_name = "FILE", global = true)]
+    pub exclude: Option<String>,
+
+    /// Merge adjacent prefixes into their supernet (lossless CIDR aggregation)
+    #[arg(short, long, global = true)]
+    pub aggregate: bool,
+
+    /// Merge prefixes until at most N entries are left, covering additional address space if needed (implies --aggregate, not supported by diff)
+    #[arg(short, long, value_name = "N", global = true)]
+    pub max_entries: Option<usize>,
+
+    /// Handling of prefixes with host bits set (e.g. 10.1.2.3/8), can be keep, truncate (10.0.0.0/8) or reject
+    #[arg(
+        short,
+        long,
+        value_name = "MODE",
+        default_value = "keep",
+        global = true
+    )]
+    pub normalize: Normalization,
+
+    /// Convert IPv6 addresses embedding IPv4 addresses into IPv4, can be none, mapped (::ffff:0:0/96) or nat64 (mapped and 64:ff9b::/96)
+    #[arg(long, value_name = "MODE", default_value = "none", global = true)]
+    pub ipv4_embedded: Ipv4Embedding,
+
+    /// Extract all IP addresses and prefixes found anywhere in the lines (e.g. of log files)
+    #[arg(long, global = true)]
+    pub extract: bool,
+
+    /// Extract only the Nth IP address or prefix of each line (implies --extract)
+    #[arg(long, value_name = "N", value_parser = at_least_one::<usize>, global = true)]
+    pub extract_nth: Option<usize>,
+
+    /// Use the text matched by the regex (its capture group named `prefix` if present) instead of the whole line
+    #[arg(long, value_name = "REGEX", global = true)]
+    pub extract_regex: Option<Regex>,
+
+    /// Fail with a non-zero exit code if the input contains invalid lines (prefixes with host bits set only with --normalize reject)
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// Print invalid lines with line number and reason to stderr
+    #[arg(long, global = true)]
+    pub report_invalid: bool,
+
+    /// List the input lines (source and line number) accounted for by each resulting prefix (not supported by diff)
+    #[arg(short = 'e', long, global = true)]
+    pub explain: bool,
+
+    /// Add the input files each prefix was read from to JSON/YAML output
+    #[arg(long, global = true)]
+    pub with_source: bool,
+
+    /// Add the trailing comments of the input lines (e.g. `10.0.0.0/8 # RFC1918`) to the output
+    #[arg(long, global = true)]
+    pub with_annotations: bool,
+
+    /// Write JSON/YAML output as document with a summary of the input and details of each prefix (family, network, netmask, first and last address, address count), not supported by diff
+    #[arg(long, global = true)]
+    pub structured: bool,
+
+    /// Print statistics of the run to stderr (inputs, duplicates, covered prefixes, outputs and timing per IP family), as text or json (not supported by diff)
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        global = true
+    )]
+    pub stats: Option<StatsFormat>,
+
+    /// Add le/ge modifiers to router prefix lists matching the more specific input prefixes each entry accounts for
+    #[arg(long, global = true)]
+    pub with_modifiers: bool,
+}
+
+impl Args {
+    /// Returns the files passed with `--file` followed by the positional paths.
+    pub fn inputs(&self) -> Vec<String> {
+        self.file.iter().chain(&self.paths).cloned().collect()
+    }
+
+    /// Returns the output format with the configured names of the firewall sets and router
+    /// prefix lists, or the template if one is specified.
+    pub fn output_format(&self) -> OutputFormat {
+        let names = FirewallNames {
+            table: self.table_name.clone(),
+            set: self.set_name.clone(),
+        };
+        let options = RouterOptions {
+            name: self.prefix_list_name.clone(),
+            seq_step: self.seq_step,
+        };
+
+        match &self.template {
+            Some(template) => OutputFormat::Template(template.clone()),
+            None => self.output_format.clone().configure(names, options),
+        }
+    }
+
+    /// Returns the options for parsing the input.
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            normalization: self.normalize,
+            ipv4_embedding: self.ipv4_embedded,
+            extract: match self.extract_nth {
+                Some(n) => Some(Extract::Nth(n)),
+                None => self.extract.then_some(Extract::All),
+            },
+            multiple: self.input_format.multiple_per_line(),
+        }
+    }
+
+    /// Returns the options for parsing the exclude file, which are the ones of the input without
+    /// extraction of the addresses and with a single prefix per line.
+    pub fn exclude_options(&self) -> ParseOptions {
+        ParseOptions {
+            extract: None,
+            multiple: false,
+            ..self.parse_options()
+        }
+    }
+}
+
+/// Parses a number of 1 or more.
+fn at_least_one<T>(s: &str) -> Result<T, String>
+where
+    T: FromStr + From<u8> + PartialOrd,
+    T::Err: fmt::Display,
+{
+    match s.parse::<T>() {
+        Ok(n) if n >= T::from(1) => Ok(n),
+        Ok(_) => Err("must be at least 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Operations comparing or combining multiple prefix lists. The results are always aggregated.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print the address space added (+) and removed (-) between two files
+    Diff {
+        #[arg(value_name = "OLD")]
+        old: String,
+
+        #[arg(value_name = "NEW")]
+        new: String,
+    },
+
+    /// Print the address space covered by all of the given files
+    Intersect {
+        #[arg(value_name = "FILE", required = true, num_args = 2..)]
+        files: Vec<String>,
+    },
+
+    /// Print the address space covered by any of the given files
+    Union {
+        #[arg(value_name = "FILE", required = true, num_args = 2..)]
+        files: Vec<String>,
+    },
 }
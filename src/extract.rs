@@ -0,0 +1,126 @@
+use ipnet::IpNet;
+
+use crate::cidr;
+
+/// Selection of the addresses and prefixes found in a line of input, used instead of parsing the
+/// whole line (e.g. for log files).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extract {
+    /// All addresses and prefixes of the line
+    All,
+    /// Only the Nth address or prefix of the line (starting at 1)
+    Nth(usize),
+}
+
+/// Returns all IPv4/IPv6 addresses and prefixes found in the line, in the order of their
+/// appearance. Surrounding text (e.g. brackets, quotes, trailing punctuation, a `label:` in front
+/// of an IPv4 address or the port of an `address:port` pair) is ignored.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::extract_prefixes;
+///
+/// let line = r#"192.0.2.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 200 from [2001:db8::1]:443."#;
+/// let prefixes: Vec<String> = extract_prefixes(line).map(|p| p.to_string()).collect();
+///
+/// assert_eq!(prefixes, vec!["192.0.2.1/32", "2001:db8::1/128"]);
+/// ```
+pub fn extract_prefixes(line: &str) -> impl Iterator<Item = IpNet> + '_ {
+    line.split(|c: char| !is_token_char(c))
+        .filter(|token| token.contains(['.', ':']))
+        .filter_map(parse_token)
+}
+
+/// Characters of IPv4/IPv6 addresses in CIDR notation (including embedded IPv4 addresses).
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_hexdigit() || matches!(c, '.' | ':' | '/')
+}
+
+fn parse_token(token: &str) -> Option<IpNet> {
+    if let Ok(prefix) = cidr::from_str(token) {
+        return Some(prefix);
+    }
+
+    // punctuation of the surrounding text, e.g. the end of a sentence
+    let trimmed = token
+        .trim_start_matches(['.', '/'])
+        .trim_end_matches(['.', ':', '/']);
+    if let Ok(prefix) = cidr::from_str(trimmed) {
+        return Some(prefix);
+    }
+
+    // an IPv4 address preceded by a label (e.g. `SRC:192.0.2.1`, hex digits of the label are
+    // part of the token) and/or followed by a port
+    let parts: Vec<&str> = trimmed.split(':').collect();
+    let mut ipv4 = parts.iter().filter(|part| part.contains('.'));
+    match (ipv4.next(), ipv4.next()) {
+        (Some(addr), None) if parts.len() <= 3 => cidr::from_str(addr).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(line: &str) -> Vec<String> {
+        extract_prefixes(line).map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_extract_from_sshd_log() {
+        assert_eq!(
+            vec!["203.0.113.5/32"],
+            extract(
+                "Oct 10 13:55:36 host sshd[1234]: Failed password for root from 203.0.113.5 port 22 ssh2"
+            )
+        );
+    }
+
+    #[test]
+    fn test_extract_from_web_server_log() {
+        assert_eq!(
+            vec!["2001:db8::1/128", "192.0.2.0/24"],
+            extract(
+                r#"2001:db8::1 - - [10/Oct/2000:13:55:36 -0700] "GET /?net=192.0.2.0/24 HTTP/1.1" 200"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_extract_with_surrounding_punctuation() {
+        assert_eq!(
+            vec![
+                "192.0.2.1/32",
+                "192.0.2.2/32",
+                "2001:db8::/32",
+                "::ffff:192.0.2.3/128",
+                "10.0.0.0/8"
+            ],
+            extract(
+                "connect to 192.0.2.1:8080, [192.0.2.2] (2001:db8::/32), ::ffff:192.0.2.3 and 10.0.0.0/8."
+            )
+        );
+    }
+
+    #[test]
+    fn test_extract_with_label() {
+        assert_eq!(
+            vec![
+                "192.0.2.1/32",
+                "203.0.113.5/32",
+                "198.51.100.7/32",
+                "2001:db8::1/128"
+            ],
+            extract(
+                "kernel: IN=eth0 SRC:192.0.2.1 client:203.0.113.5 dst:198.51.100.7:443 via:[2001:db8::1]"
+            )
+        );
+    }
+
+    #[test]
+    fn test_extract_ignores_other_tokens() {
+        assert!(extract("version 1.2.3 at 12:30:00, id cafe:babe, 10.0.0.256").is_empty());
+    }
+}
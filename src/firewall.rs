@@ -0,0 +1,224 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+/// Names of the sets, chains and tables written by the firewall output formats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirewallNames {
+    /// Table containing the sets (nftables)
+    pub table: String,
+    /// Name of the sets (nftables and ipset, suffixed with `_v4`/`_v6`), the chain (iptables)
+    /// or the table (pf)
+    pub set: String,
+}
+
+impl Default for FirewallNames {
+    fn default() -> Self {
+        FirewallNames {
+            table: "filter".to_string(),
+            set: "blocklist".to_string(),
+        }
+    }
+}
+
+/// Splits the prefixes into IPv4 and IPv6 prefixes.
+fn by_family(prefixes: &[String]) -> (Vec<&str>, Vec<&str>) {
+    prefixes
+        .iter()
+        .map(String::as_str)
+        .partition(|prefix| !prefix.contains(':'))
+}
+
+/// Writes nftables commands creating an interval set per family (`nft -f`). The table and sets
+/// are only created if missing, the elements of existing sets are replaced.
+pub fn write_nftables<W: Write>(
+    prefixes: &[String],
+    names: &FirewallNames,
+    mut w: W,
+) -> Result<()> {
+    let (ipv4, ipv6) = by_family(prefixes);
+    let table = &names.table;
+
+    writeln!(w, "add table inet {table}")?;
+    for (suffix, kind, elements) in [("v4", "ipv4_addr", ipv4), ("v6", "ipv6_addr", ipv6)] {
+        let set = format!("{}_{suffix}", names.set);
+        writeln!(
+            w,
+            "add set inet {table} {set} {{ type {kind}; flags interval; }}"
+        )?;
+        writeln!(w, "flush set inet {table} {set}")?;
+        // an empty element list is a syntax error
+        if !elements.is_empty() {
+            writeln!(w, "add element inet {table} {set} {{")?;
+            writeln!(w, "\t{}", elements.join(",\n\t"))?;
+            writeln!(w, "}}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Default maximum number of elements of an ipset set.
+const IPSET_MAXELEM: usize = 65536;
+
+/// Writes a script creating (or flushing) a `hash:net` set per family (`ipset restore`). The
+/// maximum number of elements is raised to the next power of two of the number of prefixes if
+/// they exceed ipset's default of 65536.
+pub fn write_ipset<W: Write>(prefixes: &[String], names: &FirewallNames, mut w: W) -> Result<()> {
+    let (ipv4, ipv6) = by_family(prefixes);
+
+    for (suffix, family, elements) in [("v4", "inet", ipv4), ("v6", "inet6", ipv6)] {
+        let set = format!("{}_{suffix}", names.set);
+        let maxelem = elements.len().next_power_of_two().max(IPSET_MAXELEM);
+        writeln!(
+            w,
+            "create {set} hash:net family {family} maxelem {maxelem} -exist"
+        )?;
+        writeln!(w, "flush {set}")?;
+        for prefix in elements {
+            writeln!(w, "add {set} {prefix}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes rules dropping the traffic from the prefixes of one family into a chain of the filter
+/// table (`iptables-restore --noflush` or `ip6tables-restore --noflush`, which only replaces the
+/// rules of this chain). Prefixes of the other family are skipped with a warning on stderr. The
+/// chain is not jumped to, a rule like `-I INPUT -j <chain>` has to be added separately.
+pub fn write_iptables<W: Write>(
+    prefixes: &[String],
+    names: &FirewallNames,
+    ipv6: bool,
+    mut w: W,
+) -> Result<()> {
+    let (v4, v6) = by_family(prefixes);
+    let chain = &names.set;
+    let (command, family, other, skipped) = if ipv6 {
+        ("ip6tables", v6, "iptables", v4)
+    } else {
+        ("iptables", v4, "ip6tables", v6)
+    };
+
+    if !skipped.is_empty() {
+        eprintln!(
+            "{} prefixes of the other IP family skipped, use -o {other} for them",
+            skipped.len()
+        );
+    }
+
+    writeln!(
+        w,
+        "# load with {command}-restore --noflush, jump to the chain with {command} -I INPUT -j {chain}"
+    )?;
+    writeln!(w, "*filter")?;
+    writeln!(w, ":{chain} - [0:0]")?;
+    for prefix in family {
+        writeln!(w, "-A {chain} -s {prefix} -j DROP")?;
+    }
+    writeln!(w, "COMMIT")?;
+
+    Ok(())
+}
+
+/// Writes a pf table file with one prefix of either family per line (`pfctl -T replace -f`).
+pub fn write_pf<W: Write>(prefixes: &[String], names: &FirewallNames, mut w: W) -> Result<()> {
+    writeln!(w, "# load with pfctl -t {} -T replace -f <file>", names.set)?;
+    for prefix in prefixes {
+        writeln!(w, "{prefix}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(f: impl FnOnce(&[String], &FirewallNames, &mut Vec<u8>) -> Result<()>) -> String {
+        let prefixes = vec![
+            "10.0.0.0/8".to_string(),
+            "192.0.2.1/32".to_string(),
+            "2001:db8::/32".to_string(),
+        ];
+        let names = FirewallNames {
+            table: "fw".to_string(),
+            set: "blocked".to_string(),
+        };
+        let mut buffer = Vec::new();
+        f(&prefixes, &names, &mut buffer).unwrap();
+
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_write_nftables() {
+        assert_eq!(
+            "add table inet fw\n\
+             add set inet fw blocked_v4 { type ipv4_addr; flags interval; }\n\
+             flush set inet fw blocked_v4\n\
+             add element inet fw blocked_v4 {\n\t10.0.0.0/8,\n\t192.0.2.1/32\n}\n\
+             add set inet fw blocked_v6 { type ipv6_addr; flags interval; }\n\
+             flush set inet fw blocked_v6\n\
+             add element inet fw blocked_v6 {\n\t2001:db8::/32\n}\n",
+            write(|p, n, w| write_nftables(p, n, w))
+        );
+    }
+
+    #[test]
+    fn test_write_ipset() {
+        assert_eq!(
+            "create blocked_v4 hash:net family inet maxelem 65536 -exist\nflush blocked_v4\n\
+             add blocked_v4 10.0.0.0/8\nadd blocked_v4 192.0.2.1/32\n\
+             create blocked_v6 hash:net family inet6 maxelem 65536 -exist\nflush blocked_v6\n\
+             add blocked_v6 2001:db8::/32\n",
+            write(|p, n, w| write_ipset(p, n, w))
+        );
+    }
+
+    #[test]
+    fn test_write_ipset_maxelem() {
+        let prefixes: Vec<String> = (0..70000u32)
+            .map(|n| format!("{}/32", std::net::Ipv4Addr::from(0x0a00_0000 + n)))
+            .collect();
+        let mut buffer = Vec::new();
+        write_ipset(&prefixes, &FirewallNames::default(), &mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+
+        assert!(
+            output.starts_with("create blocklist_v4 hash:net family inet maxelem 131072 -exist\n")
+        );
+        assert!(
+            output.contains("create blocklist_v6 hash:net family inet6 maxelem 65536 -exist\n")
+        );
+    }
+
+    #[test]
+    fn test_write_iptables() {
+        assert_eq!(
+            "# load with iptables-restore --noflush, jump to the chain with iptables -I INPUT -j blocked\n\
+             *filter\n:blocked - [0:0]\n\
+             -A blocked -s 10.0.0.0/8 -j DROP\n-A blocked -s 192.0.2.1/32 -j DROP\nCOMMIT\n",
+            write(|p, n, w| write_iptables(p, n, false, w))
+        );
+        assert_eq!(
+            "# load with ip6tables-restore --noflush, jump to the chain with ip6tables -I INPUT -j blocked\n\
+             *filter\n:blocked - [0:0]\n-A blocked -s 2001:db8::/32 -j DROP\nCOMMIT\n",
+            write(|p, n, w| write_iptables(p, n, true, w))
+        );
+    }
+
+    #[test]
+    fn test_write_pf() {
+        assert_eq!(
+            "# load with pfctl -t blocked -T replace -f <file>\n\
+             10.0.0.0/8\n192.0.2.1/32\n2001:db8::/32\n",
+            write(|p, n, w| write_pf(p, n, w))
+        );
+        assert_eq!(
+            "# load with pfctl -t blocked -T replace -f <file>\n",
+            write(|_, n, w| write_pf(&[], n, w))
+        );
+    }
+}
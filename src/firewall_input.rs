@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+
+/// Returns the address part of an entry of an `ipset save` dump (e.g. `10.0.0.0/8` for
+/// `add blocked 10.0.0.0/8,tcp:80 timeout 300`), `None` for other lines and `nomatch` entries.
+pub fn ipset_entry(line: &str) -> Option<&str> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("add") {
+        return None;
+    }
+
+    let entry = tokens.nth(1)?;
+    if tokens.any(|option| option == "nomatch") {
+        return None;
+    }
+
+    entry.split(',').next()
+}
+
+/// Parser for the `elements = { ... }` blocks of nftables set definitions and `add element`
+/// commands, which may span multiple lines. Only the elements of sets and maps declared with a
+/// key of IPv4/IPv6 addresses (`type ipv4_addr`, `typeof ip saddr`, ...) are returned, other
+/// sets (e.g. of ports or interfaces) are skipped.
+#[derive(Debug, Default)]
+pub struct NftablesElements {
+    /// Whether the elements of the block being read are returned, `None` outside of a block
+    block: Option<bool>,
+    /// Family and name of the table being defined (e.g. `inet filter`)
+    table: String,
+    /// Set or map being defined, its `type` statement applies to it
+    set: Option<String>,
+    /// Whether the key of a set is an address, by family, table and name of the set
+    addresses: HashMap<String, bool>,
+}
+
+impl NftablesElements {
+    /// Returns the elements of the block found in the line, without element options (e.g.
+    /// `timeout 1h`) or map values (e.g. `: drop`).
+    pub fn parse_line<'a>(&mut self, line: &'a str) -> Vec<&'a str> {
+        let mut rest = line.split('#').next().unwrap_or_default();
+        let mut elements = Vec::new();
+
+        loop {
+            if let Some(addresses) = self.block {
+                let (block, tail) = match rest.split_once('}') {
+                    Some((block, tail)) => {
+                        self.block = None;
+                        (block, Some(tail))
+                    }
+                    None => (rest, None),
+                };
+
+                if addresses {
+                    elements.extend(
+                        block
+                            .split(',')
+                            .filter_map(|element| element.split_whitespace().next()),
+                    );
+                }
+
+                match tail {
+                    Some(tail) => rest = tail,
+                    None => return elements,
+                }
+                continue;
+            }
+
+            match rest.find([';', '{', '}']) {
+                Some(idx) => {
+                    self.statement(&rest[..idx], rest[idx..].starts_with('{'));
+                    rest = &rest[idx + 1..];
+                }
+                None => {
+                    self.statement(rest, false);
+                    return elements;
+                }
+            }
+        }
+    }
+
+    /// Handles a statement outside of an element block, `opens_block` is set if it is followed
+    /// by `{`.
+    fn statement(&mut self, statement: &str, opens_block: bool) {
+        let words: Vec<&str> = statement.split_whitespace().collect();
+
+        match words[..] {
+            ["elements", "="] if opens_block => {
+                self.block = Some(self.is_address_set(self.set.clone()));
+            }
+            ["add" | "create", "element", ref name @ ..] if opens_block => {
+                self.block = Some(self.is_address_set(set_key(name)));
+            }
+            ["add" | "create", "set" | "map", ref name @ ..] => self.set = set_key(name),
+            ["add" | "create", ..] => self.set = None,
+            ["table", ref name @ ..] if opens_block => {
+                self.table = set_key(name).unwrap_or_default();
+                self.set = None;
+            }
+            ["set" | "map", name] if opens_block => {
+                self.set = Some(format!("{} {name}", self.table));
+            }
+            ["type" | "typeof", ..] => {
+                if let (Some(set), Some(addresses)) = (&self.set, declares_addresses(statement)) {
+                    self.addresses.insert(set.clone(), addresses);
+                }
+            }
+            // other blocks (e.g. chains) end the definition of the set
+            _ if opens_block => self.set = None,
+            _ => {}
+        }
+    }
+
+    fn is_address_set(&self, set: Option<String>) -> bool {
+        set.and_then(|set| self.addresses.get(&set).copied())
+            .unwrap_or_default()
+    }
+}
+
+/// Returns the family followed by the names of a table or set, e.g. `inet filter blocked` for
+/// `inet filter blocked` or `ip filter blocked` for `filter blocked` (the family defaults to
+/// `ip`).
+fn set_key(name: &[&str]) -> Option<String> {
+    match name {
+        [family, names @ ..] if matches!(names.len(), 1 | 2) && is_family(family) => {
+            Some(name.join(" "))
+        }
+        [_] | [_, _] => Some(format!("ip {}", name.join(" "))),
+        _ => None,
+    }
+}
+
+fn is_family(s: &str) -> bool {
+    matches!(s, "ip" | "ip6" | "inet" | "arp" | "bridge" | "netdev")
+}
+
+/// Returns whether the key declared by a `type` or `typeof` statement is an IPv4 or IPv6
+/// address (concatenations like `ipv4_addr . inet_service` are not), `None` for other
+/// statements.
+fn declares_addresses(statement: &str) -> Option<bool> {
+    // the key reaches until the value of a map (`: verdict`)
+    let key = statement.split(':').next().unwrap_or_default();
+    let mut words = key.split_whitespace();
+    let kind = words.next()?;
+    let key: Vec<&str> = words.collect();
+
+    match kind {
+        "type" => Some(matches!(key[..], ["ipv4_addr" | "ipv6_addr"])),
+        "typeof" => Some(matches!(key[..], ["ip" | "ip6", "saddr" | "daddr"])),
+        _ => None,
+    }
+}
+
+/// Returns the source and destination addresses of a rule of an `iptables-save` dump, lists of
+/// addresses (`-s 10.0.0.0/8,192.0.2.1`) are split. Negated addresses are skipped.
+pub fn iptables_addresses(line: &str) -> Vec<&str> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if !matches!(tokens.first(), Some(&("-A" | "-I"))) {
+        return Vec::new();
+    }
+
+    let mut addresses = Vec::new();
+    for (idx, window) in tokens.windows(2).enumerate() {
+        let is_address = matches!(
+            window[0],
+            "-s" | "--source" | "-d" | "--destination" | "--src-range" | "--dst-range"
+        );
+        let negated = idx > 0 && tokens[idx - 1] == "!";
+
+        if is_address && !negated {
+            addresses.extend(window[1].split(','));
+        }
+    }
+
+    addresses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipset_entry() {
+        assert_eq!(Some("10.0.0.0/8"), ipset_entry("add blocked 10.0.0.0/8"));
+        assert_eq!(
+            Some("192.0.2.1"),
+            ipset_entry("add blocked 192.0.2.1,tcp:80 timeout 300")
+        );
+        assert_eq!(
+            Some("192.0.2.1-192.0.2.9"),
+            ipset_entry("add range 192.0.2.1-192.0.2.9")
+        );
+        assert_eq!(None, ipset_entry("add blocked 10.1.0.0/16 nomatch"));
+        assert_eq!(None, ipset_entry("create blocked hash:net family inet"));
+        assert_eq!(None, ipset_entry("add blocked"));
+    }
+
+    #[test]
+    fn test_nftables_elements() {
+        let mut parser = NftablesElements::default();
+        let mut parse = |line| parser.parse_line(line);
+
+        assert!(parse("set blocked {").is_empty());
+        assert!(parse("type ipv4_addr; flags interval").is_empty());
+        assert_eq!(
+            vec!["10.0.0.0/8", "192.0.2.1-192.0.2.9"],
+            parse("elements = { 10.0.0.0/8, 192.0.2.1-192.0.2.9,")
+        );
+        assert_eq!(vec!["198.51.100.1"], parse("198.51.100.1 timeout 1h }"));
+        assert!(parse("}").is_empty());
+        assert_eq!(
+            vec!["2001:db8::/32", "2001:db8:1::1"],
+            parse("elements = { 2001:db8::/32 : drop, 2001:db8:1::1 } # blocked")
+        );
+    }
+
+    #[test]
+    fn test_nftables_elements_skips_other_sets() {
+        let ruleset = "table inet filter {\n\
+                       \tset ports {\n\
+                       \t\ttype inet_service\n\
+                       \t\telements = { 22, 80 }\n\
+                       \t}\n\
+                       \tset blocked_v6 { typeof ip6 saddr; elements = { 2001:db8::1 } }\n\
+                       \tset blocked_type { type ipv4_addr; elements = { 192.0.2.9 } }\n\
+                       \tset pairs { type ipv4_addr . inet_service; elements = { 192.0.2.1 . 22 } }\n\
+                       \tmap verdicts {\n\
+                       \t\ttype ipv4_addr : verdict\n\
+                       \t\telements = { 192.0.2.2 : drop,\n\
+                       \t\t\t     192.0.2.3 : accept }\n\
+                       \t}\n\
+                       \tchain input {\n\
+                       \t\ticmp type echo-request accept\n\
+                       \t\tiifname { eth0, eth1 } drop\n\
+                       \t}\n\
+                       }";
+        let mut parser = NftablesElements::default();
+        let elements: Vec<&str> = ruleset
+            .lines()
+            .flat_map(|line| parser.parse_line(line))
+            .collect();
+
+        assert_eq!(
+            vec!["2001:db8::1", "192.0.2.9", "192.0.2.2", "192.0.2.3"],
+            elements
+        );
+    }
+
+    #[test]
+    fn test_nftables_elements_by_set() {
+        let commands = "add table inet fw\n\
+                        add set inet fw blocked { type ipv4_addr; flags interval; }\n\
+                        add set inet fw ports { type inet_service; }\n\
+                        add chain inet fw input { type filter hook input priority 0; }\n\
+                        add element inet fw blocked { 10.0.0.0/8 }\n\
+                        add element inet fw ports { 22, 80 }\n\
+                        add element inet fw blocked {\n\
+                        \t192.0.2.1\n\
+                        }\n\
+                        add element inet other blocked { 198.51.100.1 }";
+        let mut parser = NftablesElements::default();
+        let elements: Vec<&str> = commands
+            .lines()
+            .flat_map(|line| parser.parse_line(line))
+            .collect();
+
+        assert_eq!(vec!["10.0.0.0/8", "192.0.2.1"], elements);
+    }
+
+    #[test]
+    fn test_iptables_addresses() {
+        assert_eq!(
+            vec!["10.0.0.0/8", "192.0.2.1/32", "198.51.100.0/24"],
+            iptables_addresses("-A INPUT -s 10.0.0.0/8,192.0.2.1/32 -d 198.51.100.0/24 -j DROP")
+        );
+        assert_eq!(
+            vec!["192.0.2.1-192.0.2.9"],
+            iptables_addresses(
+                "-A INPUT ! -s 10.0.0.0/8 -m iprange --src-range 192.0.2.1-192.0.2.9 -j DROP"
+            )
+        );
+        assert!(iptables_addresses(":INPUT ACCEPT [0:0]").is_empty());
+        assert!(iptables_addresses("*filter").is_empty());
+    }
+}
@@ -1,33 +1,32 @@
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use serde_json::Value;
 
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Read};
 
-/// Reads input lines from standard input (stdin).
-///
-/// This function reads all lines from stdin until EOF is reached and collects them in a vector of
-/// strings.
-///
-/// # Returns
+use crate::firewall_input;
+use crate::selector::Selector;
+
+/// Returns a buffered reader for standard input (stdin).
 ///
-/// Returns `Ok(Vec<String>)` containing all input lines on success,
-/// or an `Err` if an I/O error occurs during reading.
+/// The input is not read upfront, so it can be consumed line by line without holding it in
+/// memory as a whole.
 ///
 /// # Examples
 ///
 /// ```no_run
 /// use net_reduce::input;
 ///
-/// let lines = input::from_stdin().expect("Failed to read from stdin");
+/// let reader = input::from_stdin();
 /// ```
-pub fn from_stdin() -> Result<Vec<String>> {
-    read_lines(std::io::stdin().lock())
+pub fn from_stdin() -> Box<dyn BufRead> {
+    Box::new(std::io::stdin().lock())
 }
 
-/// Reads input lines from a specified file.
+/// Returns a buffered reader for a specified file.
 ///
-/// This function opens the file at the given path and reads all lines,
-/// collecting them into a vector of strings
+/// This function opens the file at the given path, the content is read on demand by the caller.
 ///
 /// # Arguments
 ///
@@ -35,30 +34,542 @@ pub fn from_stdin() -> Result<Vec<String>> {
 ///
 /// # Returns
 ///
-/// Returns `Ok(Vec<String>)` containing all lines from the file on success,
-/// or an `Err` if the file cannot be opened or an I/O error occurs.
+/// Returns `Ok(Box<dyn BufRead>)` on success, or an `Err` if the file cannot be opened.
 ///
 /// # Examples
 ///
 /// ```no_run
 /// use net_reduce::input;
 ///
-/// let lines = input::from_file("/path/to/cidrs.txt")
-///     .expect("Failed to read file");
+/// let reader = input::from_file("/path/to/cidrs.txt")
+///     .expect("Failed to open file");
 /// ```
-pub fn from_file(path: &str) -> Result<Vec<String>> {
+pub fn from_file(path: &str) -> Result<Box<dyn BufRead>> {
     let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    read_lines(reader)
+    Ok(Box::new(BufReader::new(file)))
+}
+
+/// Returns a buffered reader yielding for each line of `reader` only the text matched by `regex`.
+///
+/// If the regex has a capture group named `prefix` only the text of this group is used. Lines
+/// not matching are replaced by empty lines, so line numbers stay the same.
+///
+/// # Examples
+///
+/// ```no_run
+/// use net_reduce::input;
+/// use regex::Regex;
+///
+/// let regex = Regex::new(r"client=(?<prefix>\S+)").unwrap();
+/// let reader = input::extract_regex(input::from_stdin(), regex);
+/// ```
+pub fn extract_regex(reader: Box<dyn BufRead>, regex: Regex) -> Box<dyn BufRead> {
+    let group = regex
+        .capture_names()
+        .position(|name| name == Some("prefix"))
+        .unwrap_or(0);
+
+    map_lines(reader, move |line| {
+        regex
+            .captures(line)
+            .and_then(|captures| captures.get(group))
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default()
+    })
+}
+
+/// Returns a buffered reader yielding the entries of an `ipset save` dump (`add setname
+/// 10.0.0.0/8 timeout 300`), one per line. Only the address part of entries of sets with
+/// additional dimensions (e.g. `10.0.0.0/8,tcp:80` of `hash:net,port`) is used, entries marked
+/// with `nomatch` are skipped. Other lines are replaced by empty lines.
+pub fn from_ipset(reader: Box<dyn BufRead>) -> Box<dyn BufRead> {
+    map_lines(reader, |line| {
+        firewall_input::ipset_entry(line)
+            .unwrap_or_default()
+            .to_string()
+    })
+}
+
+/// Returns a buffered reader yielding the elements of the `elements = { ... }` blocks and
+/// `add element` commands of nftables sets of addresses (e.g. `nft list ruleset`), as comma
+/// separated list per line. Other lines are replaced by empty lines.
+pub fn from_nftables(reader: Box<dyn BufRead>) -> Box<dyn BufRead> {
+    let mut elements = firewall_input::NftablesElements::default();
+
+    map_lines(reader, move |line| elements.parse_line(line).join(", "))
+}
+
+/// Returns a buffered reader yielding the source and destination addresses of the rules of an
+/// `iptables-save` or `ip6tables-save` dump (`-s`, `-d`, `--src-range` and `--dst-range`), as
+/// comma separated list per line. Negated addresses (`! -s 10.0.0.0/8`) are skipped, other
+/// lines are replaced by empty lines.
+pub fn from_iptables(reader: Box<dyn BufRead>) -> Box<dyn BufRead> {
+    map_lines(reader, |line| {
+        firewall_input::iptables_addresses(line).join(", ")
+    })
+}
+
+/// Returns a buffered reader yielding each line of `reader` transformed by `f`. The line is
+/// passed without line break, `f` must not return line breaks to keep the line numbers.
+fn map_lines<F>(mut reader: Box<dyn BufRead>, mut f: F) -> Box<dyn BufRead>
+where
+    F: FnMut(&str) -> String + 'static,
+{
+    let mut line = String::new();
+
+    MappedLines::boxed(move |buf| {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(false);
+        }
+
+        buf.extend_from_slice(f(line.trim_end_matches(['\n', '\r'])).as_bytes());
+        buf.push(b'\n');
+
+        Ok(true)
+    })
+}
+
+/// Column of a CSV/TSV file containing the prefixes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    /// Position of the column, starting at 1
+    Index(usize),
+    /// Name of the column in the header row (case-insensitive)
+    Name(String),
+}
+
+impl std::str::FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(0) => Err("column index starts at 1".to_string()),
+            Ok(index) => Ok(Column::Index(index)),
+            Err(_) => Ok(Column::Name(s.to_string())),
+        }
+    }
+}
+
+/// Returns a buffered reader yielding for each record of the CSV/TSV `reader` only the value of
+/// the given column.
+///
+/// Fields may be quoted (`"..."`, with `""` for a literal quote) to contain the delimiter or line
+/// breaks. A column selected by name is looked up in the header row, a column selected by index
+/// treats the first row as header if its value is not a prefix. The header row and records
+/// without the column are replaced by empty lines, so line numbers stay the same.
+///
+/// # Examples
+///
+/// ```no_run
+/// use net_reduce::input::{self, Column};
+///
+/// let reader = input::from_csv(input::from_stdin(), b',', &Column::Name("prefix".into()))
+///     .expect("Failed to read header");
+/// ```
+pub fn from_csv(
+    reader: Box<dyn BufRead>,
+    delimiter: u8,
+    column: &Column,
+) -> Result<Box<dyn BufRead>> {
+    let mut records = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader)
+        .into_records()
+        .peekable();
+
+    let (index, skip_first) = match column {
+        Column::Name(name) => {
+            let header = match records.peek() {
+                Some(Ok(header)) => header,
+                Some(Err(_)) => bail!("failed to read header row"),
+                None => bail!("column {name} not found, the input is empty"),
+            };
+            let index = header
+                .iter()
+                .position(|field| field.trim().eq_ignore_ascii_case(name))
+                .with_context(|| format!("column {name} not found in header row"))?;
+
+            (index, true)
+        }
+        Column::Index(index) => {
+            let is_header = matches!(
+                records.peek(),
+                Some(Ok(first)) if first.get(index - 1).is_some_and(|v| !is_prefix(v))
+            );
+
+            (index - 1, is_header)
+        }
+    };
+
+    let mut next_line = 1;
+    let mut skip = skip_first;
+
+    Ok(MappedLines::boxed(move |buf| {
+        let Some(record) = records.next() else {
+            return Ok(false);
+        };
+        let record = record.map_err(io::Error::other)?;
+
+        // records may span multiple lines, keep the line numbers in sync with the input
+        let line = record.position().map_or(next_line, |p| p.line());
+        for _ in next_line..line {
+            buf.push(b'\n');
+        }
+        next_line = line + 1;
+
+        if !std::mem::take(&mut skip)
+            && let Some(value) = record.get(index)
+        {
+            buf.extend_from_slice(value.trim().as_bytes());
+        }
+        buf.push(b'\n');
+
+        Ok(true)
+    }))
+}
+
+/// Returns a buffered reader yielding one line for each value of the JSON document read from
+/// `reader` matching the selector.
+///
+/// String values are used as they are, objects with a `prefix` field (as written by
+/// `--with-source` or `--explain`) are represented by that field. Line numbers refer to the
+/// position of the value among the selected values.
+///
+/// # Examples
+///
+/// ```no_run
+/// use net_reduce::input;
+///
+/// let selector = "$.prefixes[*].ip_prefix".parse().unwrap();
+/// let reader = input::from_json(input::from_stdin(), &selector).expect("Invalid JSON");
+/// ```
+pub fn from_json(reader: Box<dyn BufRead>, selector: &Selector) -> Result<Box<dyn BufRead>> {
+    let document: Value = serde_json::from_reader(reader).context("failed to parse JSON input")?;
+
+    Ok(selected_lines(&document, selector))
+}
+
+/// Returns a buffered reader yielding one line for each value of the YAML document read from
+/// `reader` matching the selector, see [`from_json`].
+pub fn from_yaml(reader: Box<dyn BufRead>, selector: &Selector) -> Result<Box<dyn BufRead>> {
+    let document: Value = serde_yaml::from_reader(reader).context("failed to parse YAML input")?;
+
+    Ok(selected_lines(&document, selector))
+}
+
+fn selected_lines(document: &Value, selector: &Selector) -> Box<dyn BufRead> {
+    let mut lines = String::new();
+
+    for value in selector.select(document) {
+        let line = match value {
+            Value::String(s) => s.clone(),
+            Value::Object(map) => match map.get("prefix") {
+                Some(Value::String(prefix)) => prefix.clone(),
+                _ => value.to_string(),
+            },
+            value => value.to_string(),
+        };
+
+        // keep one line per value
+        lines.push_str(&line.replace(['\n', '\r'], " "));
+        lines.push('\n');
+    }
+
+    Box::new(io::Cursor::new(lines))
+}
+
+fn is_prefix(value: &str) -> bool {
+    let (prefixes, report) = net_reduce::parse_cidrs(&[value.to_string()]);
+    report.is_empty() && !prefixes.is_empty()
 }
 
-fn read_lines<R: BufRead>(reader: R) -> Result<Vec<String>> {
-    let mut lines = Vec::new();
+/// Reader yielding the lines produced by a function, used to transform the input line by line.
+struct MappedLines<F> {
+    /// Appends the next output to the buffer, returns `false` at the end of the input
+    next: F,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<F> MappedLines<F>
+where
+    F: FnMut(&mut Vec<u8>) -> io::Result<bool> + 'static,
+{
+    fn boxed(next: F) -> Box<dyn BufRead> {
+        Box::new(MappedLines {
+            next,
+            buf: Vec::new(),
+            pos: 0,
+        })
+    }
+}
+
+impl<F> Read for MappedLines<F>
+where
+    F: FnMut(&mut Vec<u8>) -> io::Result<bool>,
+{
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(out.len());
+        out[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl<F> BufRead for MappedLines<F>
+where
+    F: FnMut(&mut Vec<u8>) -> io::Result<bool>,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos >= self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+
+            if !(self.next)(&mut self.buf)? {
+                break;
+            }
+        }
+
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
+
+/// Expands a path with wildcards (e.g. `feeds/*.txt` or `feeds/**/*.txt`) into the matching
+/// files, sorted by name. Supported are `*`, `?`, `[...]` and `**` for any number of
+/// directories. Paths without wildcards are returned unchanged.
+///
+/// # Examples
+///
+/// ```no_run
+/// use net_reduce::input;
+///
+/// let paths = input::expand_glob("/path/to/feeds/*.txt")
+///     .expect("Failed to read directory");
+/// ```
+pub fn expand_glob(pattern: &str) -> Result<Vec<String>> {
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![pattern.to_string()]);
+    }
+
+    let mut paths = Vec::new();
+    for entry in glob::glob(pattern).with_context(|| format!("{pattern}: invalid pattern"))? {
+        let path = entry.with_context(|| format!("{pattern}: failed to read directory"))?;
+
+        if path.is_file() {
+            paths.push(path.to_string_lossy().into_owned());
+        }
+    }
+
+    if paths.is_empty() {
+        bail!("{pattern}: no matching files found");
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_regex() {
+        let input = "user=a client=192.0.2.1\nno match\nclient=10.0.0.0/8 user=b\n";
+        let regex = Regex::new(r"client=(?<prefix>\S+)").unwrap();
+
+        let mut output = String::new();
+        extract_regex(Box::new(input.as_bytes()), regex)
+            .read_to_string(&mut output)
+            .unwrap();
+
+        assert_eq!("192.0.2.1\n\n10.0.0.0/8\n", output);
+    }
 
-    for line in reader.lines() {
-        let line = line?;
-        lines.push(line);
+    #[test]
+    fn test_extract_regex_whole_match() {
+        let input = "a 192.0.2.1 b\n";
+        let regex = Regex::new(r"\d+\.\d+\.\d+\.\d+").unwrap();
+
+        let lines: Vec<String> = extract_regex(Box::new(input.as_bytes()), regex)
+            .lines()
+            .collect::<io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(vec!["192.0.2.1"], lines);
+    }
+
+    fn read_csv(input: &'static str, delimiter: u8, column: &str) -> Result<String> {
+        let mut output = String::new();
+        from_csv(
+            Box::new(input.as_bytes()),
+            delimiter,
+            &column.parse().unwrap(),
+        )?
+        .read_to_string(&mut output)?;
+
+        Ok(output)
+    }
+
+    #[test]
+    fn test_from_csv_by_name() {
+        let input = "name,Prefix\nlab,10.0.0.0/8\n\"office, 2nd floor\",192.0.2.0/24\nempty\n";
+
+        assert_eq!(
+            "\n10.0.0.0/8\n192.0.2.0/24\n\n",
+            read_csv(input, b',', "prefix").unwrap()
+        );
+        assert!(read_csv(input, b',', "address").is_err());
+    }
+
+    #[test]
+    fn test_from_csv_by_index_detects_header() {
+        assert_eq!(
+            "\n10.0.0.0/8\n",
+            read_csv("name\tprefix\nlab\t10.0.0.0/8\n", b'\t', "2").unwrap()
+        );
+        assert_eq!(
+            "10.0.0.0/8\n192.0.2.1\n",
+            read_csv("10.0.0.0/8\n192.0.2.1\n", b'\t', "1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_from_csv_keeps_line_numbers() {
+        let input = "note;prefix\n\"multi\nline\";10.0.0.0/8\nx;invalid\n";
+
+        assert_eq!(
+            "\n10.0.0.0/8\n\ninvalid\n",
+            read_csv(input, b';', "prefix").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_from_json() {
+        let input = r#"{"prefixes": [{"ip_prefix": "192.0.2.0/24"}, {"ip_prefix": 42}]}"#;
+        let selector = "$.prefixes[*].ip_prefix".parse().unwrap();
+
+        let mut output = String::new();
+        from_json(Box::new(input.as_bytes()), &selector)
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+
+        assert_eq!("192.0.2.0/24\n42\n", output);
+        assert!(from_json(Box::new("[".as_bytes()), &selector).is_err());
+    }
+
+    #[test]
+    fn test_from_yaml_with_prefix_objects() {
+        let input = "- prefix: 10.0.0.0/8\n  sources: [a.txt]\n- 192.0.2.1/32\n";
+
+        let mut output = String::new();
+        from_yaml(Box::new(input.as_bytes()), &Selector::default())
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+
+        assert_eq!("10.0.0.0/8\n192.0.2.1/32\n", output);
+    }
+
+    fn read_all(mut reader: Box<dyn BufRead>) -> String {
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+
+        output
+    }
+
+    #[test]
+    fn test_from_firewall_formats() {
+        let ipset = "create blocked hash:net family inet\nadd blocked 10.0.0.0/8\nadd blocked 192.0.2.1 timeout 300\n";
+        assert_eq!(
+            "\n10.0.0.0/8\n192.0.2.1\n",
+            read_all(from_ipset(Box::new(ipset.as_bytes())))
+        );
+
+        let nftables = "table inet filter {\n\tset blocked {\n\t\ttype ipv4_addr\n\t\telements = { 10.0.0.0/8,\n\t\t\t192.0.2.1 }\n\t}\n}\n";
+        assert_eq!(
+            "\n\n\n10.0.0.0/8\n192.0.2.1\n\n\n",
+            read_all(from_nftables(Box::new(nftables.as_bytes())))
+        );
+
+        let iptables =
+            "*filter\n-A INPUT -s 10.0.0.0/8,192.0.2.1/32 -d 198.51.100.1/32 -j DROP\nCOMMIT\n";
+        assert_eq!(
+            "\n10.0.0.0/8, 192.0.2.1/32, 198.51.100.1/32\n\n",
+            read_all(from_iptables(Box::new(iptables.as_bytes())))
+        );
+    }
+
+    #[test]
+    fn test_column_from_str() {
+        assert_eq!(Ok(Column::Index(2)), "2".parse());
+        assert_eq!(Ok(Column::Name("prefix".to_string())), "prefix".parse());
+        assert!("0".parse::<Column>().is_err());
     }
 
-    Ok(lines)
+    #[test]
+    fn test_expand_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.txt", "a.txt", "c.csv"] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+        let dir_name = dir.path().to_str().unwrap();
+
+        let paths = expand_glob(&format!("{dir_name}/*.txt")).unwrap();
+        assert_eq!(
+            vec![format!("{dir_name}/a.txt"), format!("{dir_name}/b.txt")],
+            paths
+        );
+
+        assert!(expand_glob(&format!("{dir_name}/*.json")).is_err());
+        assert_eq!(vec!["feed.txt"], expand_glob("feed.txt").unwrap());
+    }
+
+    #[test]
+    fn test_expand_glob_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("v4/2024")).unwrap();
+        for name in [
+            "feed-1.txt",
+            "feed-10.txt",
+            "feed-2.txt",
+            "v4/a.txt",
+            "v4/2024/b.txt",
+        ] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+        let dir_name = dir.path().to_str().unwrap();
+        let expand = |pattern: &str| {
+            expand_glob(&format!("{dir_name}/{pattern}"))
+                .unwrap()
+                .into_iter()
+                .map(|p| p[dir_name.len() + 1..].to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["feed-1.txt", "feed-2.txt"], expand("feed-?.txt"));
+        assert_eq!(vec!["feed-1.txt", "feed-10.txt"], expand("feed-1*.txt"));
+        assert_eq!(vec!["feed-2.txt"], expand("feed-[2-9].txt"));
+        assert_eq!(
+            vec![
+                "feed-1.txt",
+                "feed-10.txt",
+                "feed-2.txt",
+                "v4/2024/b.txt",
+                "v4/a.txt"
+            ],
+            expand("**/*.txt")
+        );
+        assert_eq!(vec!["v4/2024/b.txt"], expand("v4/*/*.txt"));
+        assert!(expand_glob(&format!("{dir_name}/feed-[.txt")).is_err());
+    }
 }
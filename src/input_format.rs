@@ -0,0 +1,79 @@
+use std::io::BufRead;
+
+use anyhow::Result;
+
+use crate::input::{self, Column};
+use crate::selector::Selector;
+
+/// Input format specifies how the prefixes are read from each input
+#[derive(Debug, Clone, Copy)]
+pub enum InputFormat {
+    /// One prefix per line
+    List,
+    /// Comma separated values, the prefixes are read from a single column
+    Csv,
+    /// Tab separated values, the prefixes are read from a single column
+    Tsv,
+    /// JSON document, the prefixes are selected with a JSONPath-like selector
+    Json,
+    /// YAML document, the prefixes are selected with a JSONPath-like selector
+    Yaml,
+    /// `ipset save` dump, the entries of all sets are read
+    Ipset,
+    /// nftables ruleset (e.g. `nft list ruleset`), the elements of all sets are read
+    Nftables,
+    /// `iptables-save` or `ip6tables-save` dump, the source and destination addresses of all
+    /// rules are read
+    Iptables,
+}
+
+impl InputFormat {
+    /// Returns a reader yielding one prefix per line for the input in this format. The column is
+    /// only used by CSV and TSV input, the first column is used if none is specified. The
+    /// selector is only used by JSON and YAML input, the elements of an array at the root of the
+    /// document are used if none is specified.
+    pub fn lines(
+        &self,
+        reader: Box<dyn BufRead>,
+        column: Option<&Column>,
+        selector: Option<&Selector>,
+    ) -> Result<Box<dyn BufRead>> {
+        let column = column.unwrap_or(&Column::Index(1));
+        let default_selector = Selector::default();
+        let selector = selector.unwrap_or(&default_selector);
+
+        match self {
+            InputFormat::List => Ok(reader),
+            InputFormat::Csv => input::from_csv(reader, b',', column),
+            InputFormat::Tsv => input::from_csv(reader, b'\t', column),
+            InputFormat::Json => input::from_json(reader, selector),
+            InputFormat::Yaml => input::from_yaml(reader, selector),
+            InputFormat::Ipset => Ok(input::from_ipset(reader)),
+            InputFormat::Nftables => Ok(input::from_nftables(reader)),
+            InputFormat::Iptables => Ok(input::from_iptables(reader)),
+        }
+    }
+
+    /// Returns `true` if a line of the input may contain multiple prefixes.
+    pub fn multiple_per_line(&self) -> bool {
+        matches!(self, InputFormat::Nftables | InputFormat::Iptables)
+    }
+}
+
+impl std::str::FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "list" => Ok(InputFormat::List),
+            "csv" => Ok(InputFormat::Csv),
+            "tsv" => Ok(InputFormat::Tsv),
+            "json" => Ok(InputFormat::Json),
+            "yaml" | "yml" => Ok(InputFormat::Yaml),
+            "ipset" => Ok(InputFormat::Ipset),
+            "nftables" | "nft" => Ok(InputFormat::Nftables),
+            "iptables" => Ok(InputFormat::Iptables),
+            _ => Err(anyhow::anyhow!("Unknown input format: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+
+/// IPv4-mapped addresses (::ffff:0:0/96, RFC 4291)
+const MAPPED: Ipv6Addr = Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0);
+/// Well-known prefix of NAT64 (64:ff9b::/96, RFC 6052)
+const NAT64: Ipv6Addr = Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0);
+/// Length of the prefixes above, the IPv4 address is embedded in the remaining 32 bits
+const EMBEDDING_PREFIX_LEN: u8 = 96;
+
+/// IPv6 prefixes embedding IPv4 addresses which are converted into their IPv4 family, so they
+/// are reduced together with the IPv4 prefixes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Ipv4Embedding {
+    /// Keep all IPv6 prefixes as they are
+    #[default]
+    None,
+    /// Convert IPv4-mapped prefixes (e.g. ::ffff:192.0.2.1 becomes 192.0.2.1)
+    Mapped,
+    /// Convert IPv4-mapped and NAT64 prefixes (e.g. 64:ff9b::192.0.2.0/120 becomes 192.0.2.0/24)
+    Nat64,
+}
+
+impl Ipv4Embedding {
+    /// Converts the prefix into its IPv4 family if it embeds an IPv4 prefix, other prefixes are
+    /// returned unchanged.
+    pub fn canonicalize(self, prefix: IpNet) -> IpNet {
+        let IpNet::V6(net) = prefix else {
+            return prefix;
+        };
+
+        if net.prefix_len() < EMBEDDING_PREFIX_LEN || !self.embeds_ipv4(&net) {
+            return prefix;
+        }
+
+        let addr = Ipv4Addr::from(u128::from(net.addr()) as u32);
+        let net = Ipv4Net::new(addr, net.prefix_len() - EMBEDDING_PREFIX_LEN)
+            .expect("prefix length is at most 32");
+
+        IpNet::V4(net)
+    }
+
+    fn embeds_ipv4(self, net: &Ipv6Net) -> bool {
+        let embedding = |addr: Ipv6Addr| {
+            Ipv6Net::new(addr, EMBEDDING_PREFIX_LEN)
+                .expect("prefix length is valid")
+                .contains(net)
+        };
+
+        match self {
+            Ipv4Embedding::None => false,
+            Ipv4Embedding::Mapped => embedding(MAPPED),
+            Ipv4Embedding::Nat64 => embedding(MAPPED) || embedding(NAT64),
+        }
+    }
+}
+
+impl std::str::FromStr for Ipv4Embedding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Ipv4Embedding::None),
+            "mapped" => Ok(Ipv4Embedding::Mapped),
+            "nat64" => Ok(Ipv4Embedding::Nat64),
+            _ => Err(format!("Unknown IPv4 embedding: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn canonicalize(embedding: Ipv4Embedding, prefix: &str) -> String {
+        embedding.canonicalize(prefix.parse().unwrap()).to_string()
+    }
+
+    #[test]
+    fn test_canonicalize_mapped() {
+        let embedding = Ipv4Embedding::Mapped;
+
+        assert_eq!(
+            "192.0.2.1/32",
+            canonicalize(embedding, "::ffff:192.0.2.1/128")
+        );
+        assert_eq!(
+            "192.0.2.0/24",
+            canonicalize(embedding, "::ffff:192.0.2.0/120")
+        );
+        assert_eq!("0.0.0.0/0", canonicalize(embedding, "::ffff:0:0/96"));
+        assert_eq!(
+            "::ffff:0.0.0.0/95",
+            canonicalize(embedding, "::ffff:0:0/95")
+        );
+        assert_eq!(
+            "64:ff9b::c000:201/128",
+            canonicalize(embedding, "64:ff9b::192.0.2.1/128")
+        );
+        assert_eq!("192.0.2.1/32", canonicalize(embedding, "192.0.2.1/32"));
+    }
+
+    #[test]
+    fn test_canonicalize_nat64() {
+        let embedding = Ipv4Embedding::Nat64;
+
+        assert_eq!(
+            "192.0.2.1/32",
+            canonicalize(embedding, "::ffff:192.0.2.1/128")
+        );
+        assert_eq!(
+            "192.0.2.0/24",
+            canonicalize(embedding, "64:ff9b::192.0.2.0/120")
+        );
+        assert_eq!(
+            "2001:db8::1/128",
+            canonicalize(embedding, "2001:db8::1/128")
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_none() {
+        assert_eq!(
+            "::ffff:192.0.2.1/128",
+            canonicalize(Ipv4Embedding::None, "::ffff:192.0.2.1/128")
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(Ipv4Embedding::from_str("none"), Ok(Ipv4Embedding::None));
+        assert_eq!(Ipv4Embedding::from_str("Mapped"), Ok(Ipv4Embedding::Mapped));
+        assert_eq!(Ipv4Embedding::from_str("nat64"), Ok(Ipv4Embedding::Nat64));
+        assert_eq!(
+            Ipv4Embedding::from_str("6to4"),
+            Err("Unknown IPv4 embedding: 6to4".to_string())
+        );
+    }
+}
@@ -1,9 +1,26 @@
+mod build_stats;
 mod cidr;
+mod extract;
+mod ipv4_embedding;
+mod lossy_aggregation;
+mod provenance;
 mod reduce_trie;
+mod report;
+mod sort_order;
 
 use ipnet::IpNet;
+use rayon::prelude::*;
 
-use crate::reduce_trie::ReduceTrie;
+pub use ipnet;
+
+pub use crate::build_stats::{BuildStats, FamilyStats};
+pub use crate::cidr::{Normalization, ParseError, ParseOptions};
+pub use crate::extract::{Extract, extract_prefixes};
+pub use crate::ipv4_embedding::Ipv4Embedding;
+pub use crate::provenance::{Explanation, Origin, Provenance, Recorded};
+pub use crate::reduce_trie::ReduceTrie;
+pub use crate::report::{InvalidLine, ParseReport};
+pub use crate::sort_order::SortOrder;
 
 /// Reduces a list of CIDR notations and IP addresses by removing redundant entries.
 ///
@@ -18,7 +35,8 @@ use crate::reduce_trie::ReduceTrie;
 /// # Returns
 ///
 /// A vector of strings containing the reduced set of CIDR notations. All entries
-/// are returned in CIDR format (individual IPs are converted to /32 or /128).
+/// are returned in CIDR format (individual IPs are converted to /32 or /128) and
+/// sorted by IP family, address and prefix length.
 ///
 /// # Examples
 ///
@@ -36,16 +54,328 @@ use crate::reduce_trie::ReduceTrie;
 /// assert_eq!(result.len(), 2);  // Only /16 and /8 remain
 /// ```
 pub fn reduce_cidrs(lines: Vec<String>) -> Vec<String> {
-    let prefixes = lines
-        .iter()
-        .filter_map(|line| cidr::from_str(line))
-        .collect::<Vec<IpNet>>();
+    let (prefixes, _) = parse_cidrs(&lines);
+
+    to_strings(reduce_prefixes(prefixes))
+}
+
+/// Aggregates a list of CIDR notations and IP addresses into a minimal set of CIDR blocks.
+///
+/// In addition to the reduction done by [`reduce_cidrs`], adjacent prefixes are merged into
+/// their common supernet (e.g. 10.0.0.0/25 and 10.0.0.128/25 become 10.0.0.0/24). The result
+/// covers exactly the same address space as the input.
+///
+/// # Arguments
+///
+/// * `lines` - A vector of strings containing IP addresses and/or CIDR notations. Invalid entries are silently ignored.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::aggregate_cidrs;
+///
+/// let input = vec![
+///     "10.0.0.0/25".to_string(),
+///     "10.0.0.128/26".to_string(),
+///     "10.0.0.192/26".to_string(),
+/// ];
+///
+/// let result = aggregate_cidrs(input);
+/// assert_eq!(result, vec!["10.0.0.0/24".to_string()]);
+/// ```
+pub fn aggregate_cidrs(lines: Vec<String>) -> Vec<String> {
+    let (prefixes, _) = parse_cidrs(&lines);
+
+    to_strings(aggregate_prefixes(prefixes))
+}
+
+/// Aggregates a list of CIDR notations and IP addresses into at most `max_entries` CIDR blocks.
+///
+/// After lossless aggregation (see [`aggregate_cidrs`]) neighbouring prefixes are merged into
+/// their common supernet until the target is met, always picking the merge which adds the least
+/// addresses not covered by the input. The number of entries can not drop below one per IP family.
+///
+/// # Returns
+///
+/// A tuple of the aggregated CIDR notations and the number of addresses covered in addition to
+/// the input.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::aggregate_cidrs_lossy;
+///
+/// let input = vec![
+///     "10.0.0.0/24".to_string(),
+///     "10.0.2.0/24".to_string(),
+///     "10.0.8.0/24".to_string(),
+/// ];
+///
+/// let (result, added) = aggregate_cidrs_lossy(input, 2);
+/// assert_eq!(result, vec!["10.0.0.0/22".to_string(), "10.0.8.0/24".to_string()]);
+/// assert_eq!(added, 512);
+/// ```
+pub fn aggregate_cidrs_lossy(lines: Vec<String>, max_entries: usize) -> (Vec<String>, u128) {
+    let (prefixes, _) = parse_cidrs(&lines);
+    let (prefixes, added) = aggregate_prefixes_lossy(prefixes, max_entries);
+
+    (to_strings(prefixes), added)
+}
+
+/// Removes the address space of `excluded` from the reduced list of CIDR notations and IP addresses.
+///
+/// Prefixes which are only partially excluded are split into the minimal set of CIDR blocks
+/// covering the remaining addresses.
+///
+/// # Arguments
+///
+/// * `lines` - A vector of strings containing IP addresses and/or CIDR notations. Invalid entries are silently ignored.
+/// * `excluded` - A vector of strings containing the IP addresses and/or CIDR notations to remove. Invalid entries are silently ignored.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::exclude_cidrs;
+///
+/// let input = vec!["10.0.0.0/8".to_string()];
+/// let excluded = vec!["10.1.0.0/16".to_string()];
+///
+/// let result = exclude_cidrs(input, excluded);
+/// assert_eq!(result.len(), 8);
+/// assert_eq!(result[0], "10.0.0.0/16");
+/// assert_eq!(result[7], "10.128.0.0/9");
+/// ```
+pub fn exclude_cidrs(lines: Vec<String>, excluded: Vec<String>) -> Vec<String> {
+    let (prefixes, _) = parse_cidrs(&lines);
+    let (excluded, _) = parse_cidrs(&excluded);
+
+    to_strings(exclude_prefixes(prefixes, excluded))
+}
+
+/// Returns the minimal set of CIDR blocks covering all addresses contained in every list.
+///
+/// # Arguments
+///
+/// * `lists` - Vectors of strings containing IP addresses and/or CIDR notations. Invalid entries are silently ignored. Without any list the result is empty.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::intersect_cidrs;
+///
+/// let a = vec!["10.0.0.0/16".to_string(), "192.168.1.1".to_string()];
+/// let b = vec!["10.0.1.0/24".to_string(), "10.0.2.0/24".to_string()];
+/// let c = vec!["10.0.0.0/8".to_string()];
+///
+/// let result = intersect_cidrs([a, b, c]);
+/// assert_eq!(result, vec!["10.0.1.0/24".to_string(), "10.0.2.0/24".to_string()]);
+/// ```
+pub fn intersect_cidrs(lists: impl IntoIterator<Item = Vec<String>>) -> Vec<String> {
+    let lists = lists.into_iter().map(|lines| parse_cidrs(&lines).0);
+
+    to_strings(intersect_prefixes(lists))
+}
+
+/// Returns the minimal set of CIDR blocks covering all addresses contained in any of the lists.
+///
+/// # Arguments
+///
+/// * `lists` - Vectors of strings containing IP addresses and/or CIDR notations. Invalid entries are silently ignored.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::union_cidrs;
+///
+/// let a = vec!["10.0.0.0/25".to_string(), "192.168.1.1".to_string()];
+/// let b = vec!["10.0.0.128/25".to_string(), "192.168.0.0/16".to_string()];
+/// let c = vec!["192.168.1.0/24".to_string()];
+///
+/// let result = union_cidrs([a, b, c]);
+/// assert_eq!(result, vec!["10.0.0.0/24".to_string(), "192.168.0.0/16".to_string()]);
+/// ```
+pub fn union_cidrs(lists: impl IntoIterator<Item = Vec<String>>) -> Vec<String> {
+    let lists = lists.into_iter().map(|lines| parse_cidrs(&lines).0);
+
+    to_strings(union_prefixes(lists))
+}
+
+/// Compares two lists by the address space they cover.
+///
+/// # Arguments
+///
+/// * `old`, `new` - Vectors of strings containing IP addresses and/or CIDR notations. Invalid entries are silently ignored.
+///
+/// # Returns
+///
+/// A tuple of the minimal CIDR blocks covering the addresses only contained in `new` (added)
+/// and the ones covering the addresses only contained in `old` (removed).
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::diff_cidrs;
+///
+/// let old = vec!["10.0.0.0/24".to_string(), "192.168.1.1".to_string()];
+/// let new = vec!["10.0.0.0/23".to_string()];
+///
+/// let (added, removed) = diff_cidrs(old, new);
+/// assert_eq!(added, vec!["10.0.1.0/24".to_string()]);
+/// assert_eq!(removed, vec!["192.168.1.1/32".to_string()]);
+/// ```
+pub fn diff_cidrs(old: Vec<String>, new: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let (old, _) = parse_cidrs(&old);
+    let (new, _) = parse_cidrs(&new);
+    let (added, removed) = diff_prefixes(old, new);
+
+    (to_strings(added), to_strings(removed))
+}
+
+/// Parses a list of CIDR notations and IP addresses.
+///
+/// In contrast to [`reduce_cidrs`] invalid lines are not silently ignored but collected in a
+/// [`ParseReport`] with their line number (starting at 1), the raw text and the reason they were
+/// rejected. Blank lines and comments are skipped without being reported. Address ranges (e.g.
+/// `192.0.2.10-192.0.2.200`) are decomposed into the minimal set of prefixes covering them.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::{parse_cidrs, ParseError};
+///
+/// let input = vec![
+///     "10.0.0.0/8".to_string(),
+///     "192,45.3.1".to_string(),
+///     "10.0.0.0/33".to_string(),
+/// ];
+///
+/// let (prefixes, report) = parse_cidrs(&input);
+/// assert_eq!(prefixes.len(), 1);
+/// assert_eq!(report.invalid.len(), 2);
+/// assert_eq!(report.invalid[0].line_number, 2);
+/// assert_eq!(report.invalid[0].error, ParseError::InvalidAddress);
+/// ```
+pub fn parse_cidrs(lines: &[String]) -> (Vec<IpNet>, ParseReport) {
+    parse_cidrs_with_options(lines, ParseOptions::default())
+}
+
+/// Parses a list of CIDR notations and IP addresses like [`parse_cidrs`] using the given
+/// options.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::{parse_cidrs_with_options, Normalization, ParseError, ParseOptions};
+///
+/// let input = vec!["10.1.2.3/8".to_string()];
+///
+/// let options = ParseOptions {
+///     normalization: Normalization::Truncate,
+///     ..Default::default()
+/// };
+/// let (prefixes, _) = parse_cidrs_with_options(&input, options);
+/// assert_eq!(prefixes[0].to_string(), "10.0.0.0/8");
+///
+/// let options = ParseOptions {
+///     normalization: Normalization::Reject,
+///     ..Default::default()
+/// };
+/// let (_, report) = parse_cidrs_with_options(&input, options);
+/// assert_eq!(report.invalid[0].error, ParseError::HostBitsSet);
+/// ```
+pub fn parse_cidrs_with_options(
+    lines: &[String],
+    options: ParseOptions,
+) -> (Vec<IpNet>, ParseReport) {
+    let mut prefixes = Vec::with_capacity(lines.len());
+    let mut report = ParseReport::default();
+
+    for (idx, line) in lines.iter().enumerate() {
+        match cidr::parse_line(line, options) {
+            Some(Ok(parsed)) => prefixes.extend(parsed),
+            Some(Err(e)) => report.reject(idx + 1, line, e),
+            None => {}
+        }
+    }
+
+    (prefixes, report)
+}
+
+/// Removes prefixes covered by less specific ones, see [`reduce_cidrs`].
+pub fn reduce_prefixes(prefixes: Vec<IpNet>) -> Vec<IpNet> {
+    ReduceTrie::from_prefixes(prefixes).get_all_prefixes()
+}
+
+/// Reduces the prefixes and removes the address space of `excluded`, see [`exclude_cidrs`].
+pub fn exclude_prefixes(prefixes: Vec<IpNet>, excluded: Vec<IpNet>) -> Vec<IpNet> {
+    let mut trie = ReduceTrie::from_prefixes(prefixes);
+    trie.exclude(&ReduceTrie::from_prefixes(excluded));
+
+    trie.get_all_prefixes()
+}
+
+/// Returns the aggregated intersection of the prefix lists, see [`intersect_cidrs`].
+pub fn intersect_prefixes(lists: impl IntoIterator<Item = Vec<IpNet>>) -> Vec<IpNet> {
+    combine(lists, ReduceTrie::intersect)
+}
+
+/// Returns the aggregated union of the prefix lists, see [`union_cidrs`].
+pub fn union_prefixes(lists: impl IntoIterator<Item = Vec<IpNet>>) -> Vec<IpNet> {
+    combine(lists, ReduceTrie::union)
+}
+
+/// Builds a trie of each list in parallel and combines them with `op` in the order given.
+fn combine(
+    lists: impl IntoIterator<Item = Vec<IpNet>>,
+    op: fn(&mut ReduceTrie, &ReduceTrie),
+) -> Vec<IpNet> {
+    let lists: Vec<_> = lists.into_iter().collect();
+    let tries: Vec<_> = lists
+        .into_par_iter()
+        .map(ReduceTrie::from_prefixes)
+        .collect();
+
+    let mut tries = tries.into_iter();
+    let Some(mut result) = tries.next() else {
+        return Vec::new();
+    };
+    for trie in tries {
+        op(&mut result, &trie);
+    }
+    result.aggregate();
+
+    result.get_all_prefixes()
+}
+
+/// Returns the aggregated prefixes added and removed between both prefix lists, see [`diff_cidrs`].
+pub fn diff_prefixes(old: Vec<IpNet>, new: Vec<IpNet>) -> (Vec<IpNet>, Vec<IpNet>) {
+    let (old, new) = rayon::join(
+        || ReduceTrie::from_prefixes(old),
+        || ReduceTrie::from_prefixes(new),
+    );
+    let (added, removed) = old.diff(&new);
+
+    (added.get_all_prefixes(), removed.get_all_prefixes())
+}
+
+/// Reduces and merges adjacent prefixes into their supernet, see [`aggregate_cidrs`].
+pub fn aggregate_prefixes(prefixes: Vec<IpNet>) -> Vec<IpNet> {
+    let mut trie = ReduceTrie::from_prefixes(prefixes);
+    trie.aggregate();
+
+    trie.get_all_prefixes()
+}
 
-    ReduceTrie::from_prefixes(prefixes)
-        .get_all_prefixes()
-        .iter()
-        .map(|p| p.to_string())
-        .collect()
+/// Aggregates the prefixes into at most `max_entries` prefixes, see [`aggregate_cidrs_lossy`].
+pub fn aggregate_prefixes_lossy(prefixes: Vec<IpNet>, max_entries: usize) -> (Vec<IpNet>, u128) {
+    let mut trie = ReduceTrie::from_prefixes(prefixes);
+    let added = trie.aggregate_lossy(max_entries);
+
+    (trie.get_all_prefixes(), added)
+}
+
+fn to_strings(prefixes: Vec<IpNet>) -> Vec<String> {
+    prefixes.iter().map(|p| p.to_string()).collect()
 }
 
 #[cfg(test)]
@@ -98,4 +428,382 @@ mod tests {
             "duplicate host entries (plain IP and /32 CIDR) must collapse into a single result"
         );
     }
+
+    #[test]
+    fn test_aggregate_cidrs() {
+        let lines = vec![
+            "10.0.0.0/25".to_string(),
+            "10.0.0.128/25".to_string(),
+            "10.0.1.0/24".to_string(),
+            "10.0.2.1".to_string(),
+            "10.0.2.0".to_string(),
+            "10.0.3.0/24".to_string(),
+            "2001:db8::/33".to_string(),
+            "2001:db8:8000::/33".to_string(),
+            "2001:db8:1::1".to_string(),
+        ];
+
+        let mut result = aggregate_cidrs(lines);
+        result.sort();
+
+        assert_eq!(
+            vec![
+                "10.0.0.0/23".to_string(),
+                "10.0.2.0/31".to_string(),
+                "10.0.3.0/24".to_string(),
+                "2001:db8::/32".to_string(),
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn test_aggregate_cidrs_keeps_address_space() {
+        let lines = vec![
+            "192.168.0.0/24".to_string(),
+            "192.168.2.0/24".to_string(),
+            "192.168.1.1".to_string(),
+        ];
+
+        let mut result = aggregate_cidrs(lines);
+        result.sort();
+
+        assert_eq!(
+            vec![
+                "192.168.0.0/24".to_string(),
+                "192.168.1.1/32".to_string(),
+                "192.168.2.0/24".to_string(),
+            ],
+            result,
+            "prefixes which are not siblings must not be merged"
+        );
+    }
+
+    #[test]
+    fn test_aggregate_cidrs_lossy() {
+        let lines = vec![
+            "10.0.0.0/25".to_string(),
+            "10.0.0.128/25".to_string(),
+            "10.0.2.0/24".to_string(),
+            "10.1.0.1".to_string(),
+            "2001:db8::/48".to_string(),
+        ];
+
+        let (mut result, added) = aggregate_cidrs_lossy(lines, 3);
+        result.sort();
+
+        assert_eq!(
+            vec![
+                "10.0.0.0/22".to_string(),
+                "10.1.0.1/32".to_string(),
+                "2001:db8::/48".to_string(),
+            ],
+            result
+        );
+        assert_eq!(512, added);
+    }
+
+    #[test]
+    fn test_parse_cidrs_reports_invalid_lines() {
+        let lines = vec![
+            "10.0.0.0/8".to_string(),
+            "".to_string(),
+            "192,45.3.1".to_string(),
+            "10.0.0.0/33".to_string(),
+            "2001:db8::/64".to_string(),
+        ];
+
+        let (prefixes, report) = parse_cidrs(&lines);
+
+        assert_eq!(
+            vec![
+                "10.0.0.0/8".parse::<IpNet>().unwrap(),
+                "2001:db8::/64".parse::<IpNet>().unwrap(),
+            ],
+            prefixes
+        );
+        assert_eq!(
+            vec![
+                InvalidLine {
+                    line_number: 3,
+                    text: "192,45.3.1".to_string(),
+                    error: ParseError::InvalidAddress,
+                },
+                InvalidLine {
+                    line_number: 4,
+                    text: "10.0.0.0/33".to_string(),
+                    error: ParseError::PrefixLengthOutOfRange { len: 33, max: 32 },
+                },
+            ],
+            report.invalid
+        );
+    }
+
+    #[test]
+    fn test_reduce_cidrs_with_comments() {
+        let lines = vec![
+            "# private networks".to_string(),
+            "10.0.0.0/8 # RFC1918".to_string(),
+            "; covered".to_string(),
+            "10.1.0.0/16 ; lab".to_string(),
+            "// documentation".to_string(),
+            "2001:db8::/32 // RFC3849".to_string(),
+        ];
+
+        let (_, report) = parse_cidrs(&lines);
+        assert!(report.is_empty());
+        assert_eq!(
+            vec!["10.0.0.0/8".to_string(), "2001:db8::/32".to_string()],
+            reduce_cidrs(lines)
+        );
+    }
+
+    #[test]
+    fn test_reduce_trie_from_reader() {
+        let input =
+            "192.168.1.1\r\n192.168.0.0/16\n\n10.0.0.1\n10.0.0.1\n192,45.3.1\n2001:db8::/32\n";
+
+        let (trie, report) = ReduceTrie::from_reader(input.as_bytes()).unwrap();
+        let mut result = to_strings(trie.get_all_prefixes());
+        result.sort();
+
+        assert_eq!(
+            vec![
+                "10.0.0.1/32".to_string(),
+                "192.168.0.0/16".to_string(),
+                "2001:db8::/32".to_string(),
+            ],
+            result
+        );
+        assert_eq!(
+            vec![InvalidLine {
+                line_number: 6,
+                text: "192,45.3.1".to_string(),
+                error: ParseError::InvalidAddress,
+            }],
+            report.invalid
+        );
+        assert_eq!(5, report.prefixes);
+    }
+
+    #[test]
+    fn test_exclude_cidrs() {
+        let lines = vec![
+            "10.0.0.0/8".to_string(),
+            "192.168.0.0/30".to_string(),
+            "192.168.1.1".to_string(),
+            "192.168.1.2".to_string(),
+            "2001:db8::/126".to_string(),
+        ];
+        let excluded = vec![
+            "10.1.0.0/16".to_string(),
+            "10.2.3.4".to_string(),
+            "192.168.0.1".to_string(),
+            "192.168.1.0/31".to_string(),
+            "2001:db8::/64".to_string(),
+        ];
+
+        let mut result = exclude_cidrs(lines, excluded);
+        result.sort();
+
+        let mut expected = vec![
+            "10.0.0.0/16".to_string(),
+            "10.2.0.0/23".to_string(),
+            "10.2.2.0/24".to_string(),
+            "10.2.3.0/30".to_string(),
+            "10.2.3.5/32".to_string(),
+            "10.2.3.6/31".to_string(),
+            "10.2.3.8/29".to_string(),
+            "10.2.3.16/28".to_string(),
+            "10.2.3.32/27".to_string(),
+            "10.2.3.64/26".to_string(),
+            "10.2.3.128/25".to_string(),
+            "10.2.4.0/22".to_string(),
+            "10.2.8.0/21".to_string(),
+            "10.2.16.0/20".to_string(),
+            "10.2.32.0/19".to_string(),
+            "10.2.64.0/18".to_string(),
+            "10.2.128.0/17".to_string(),
+            "10.3.0.0/16".to_string(),
+            "10.4.0.0/14".to_string(),
+            "10.8.0.0/13".to_string(),
+            "10.16.0.0/12".to_string(),
+            "10.32.0.0/11".to_string(),
+            "10.64.0.0/10".to_string(),
+            "10.128.0.0/9".to_string(),
+            "192.168.0.0/32".to_string(),
+            "192.168.0.2/31".to_string(),
+            "192.168.1.2/32".to_string(),
+        ];
+        expected.sort();
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_intersect_cidrs() {
+        let a = vec![
+            "10.0.0.0/8".to_string(),
+            "172.16.0.0/24".to_string(),
+            "192.168.1.1".to_string(),
+            "192.168.1.2".to_string(),
+            "2001:db8::/64".to_string(),
+        ];
+        let b = vec![
+            "10.1.0.0/16".to_string(),
+            "10.2.3.4".to_string(),
+            "172.16.0.0/16".to_string(),
+            "192.168.1.2".to_string(),
+            "192.168.2.2".to_string(),
+            "2001:db8:1::/64".to_string(),
+        ];
+
+        let mut result = intersect_cidrs([a, b]);
+        result.sort();
+
+        assert_eq!(
+            vec![
+                "10.1.0.0/16".to_string(),
+                "10.2.3.4/32".to_string(),
+                "172.16.0.0/24".to_string(),
+                "192.168.1.2/32".to_string(),
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn test_union_cidrs() {
+        let a = vec![
+            "10.0.0.0/25".to_string(),
+            "192.168.1.1".to_string(),
+            "192.168.1.2".to_string(),
+            "2001:db8::1".to_string(),
+        ];
+        let b = vec![
+            "10.0.0.128/25".to_string(),
+            "10.0.1.1".to_string(),
+            "192.168.1.0/30".to_string(),
+            "2001:db8::1".to_string(),
+        ];
+
+        let mut result = union_cidrs([a, b]);
+        result.sort();
+
+        assert_eq!(
+            vec![
+                "10.0.0.0/24".to_string(),
+                "10.0.1.1/32".to_string(),
+                "192.168.1.0/30".to_string(),
+                "2001:db8::1/128".to_string(),
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn test_combine_multiple_lists() {
+        let lists = || {
+            vec![
+                vec!["10.0.0.0/8".to_string(), "192.168.0.0/24".to_string()],
+                vec!["10.1.0.0/16".to_string(), "192.168.0.128/25".to_string()],
+                vec!["10.1.2.0/24".to_string(), "192.168.0.0/16".to_string()],
+            ]
+        };
+
+        assert_eq!(
+            vec!["10.1.2.0/24".to_string(), "192.168.0.128/25".to_string()],
+            intersect_cidrs(lists())
+        );
+        assert_eq!(
+            vec!["10.0.0.0/8".to_string(), "192.168.0.0/16".to_string()],
+            union_cidrs(lists())
+        );
+        assert!(intersect_cidrs(Vec::new()).is_empty());
+        assert!(union_cidrs(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_diff_cidrs() {
+        let old = vec![
+            "10.0.0.0/8".to_string(),
+            "192.168.1.1".to_string(),
+            "2001:db8::/64".to_string(),
+        ];
+        let new = vec![
+            "10.0.0.0/9".to_string(),
+            "10.128.0.0/10".to_string(),
+            "10.192.0.0/10".to_string(),
+            "10.1.0.0/16".to_string(),
+            "192.168.1.2".to_string(),
+            "2001:db8::/63".to_string(),
+        ];
+
+        let (added, removed) = diff_cidrs(old, new);
+
+        assert_eq!(
+            vec![
+                "192.168.1.2/32".to_string(),
+                "2001:db8:0:1::/64".to_string()
+            ],
+            added
+        );
+        assert_eq!(vec!["192.168.1.1/32".to_string()], removed);
+    }
+
+    #[test]
+    fn test_reduce_cidrs_sorted_output() {
+        let lines = vec![
+            "2001:db8::1".to_string(),
+            "192.168.1.1".to_string(),
+            "10.0.0.0/8".to_string(),
+            "10.0.0.1".to_string(),
+            "2001:db8:1::/48".to_string(),
+            "172.16.0.0/12".to_string(),
+        ];
+
+        assert_eq!(
+            vec![
+                "10.0.0.0/8".to_string(),
+                "172.16.0.0/12".to_string(),
+                "192.168.1.1/32".to_string(),
+                "2001:db8::1/128".to_string(),
+                "2001:db8:1::/48".to_string(),
+            ],
+            reduce_cidrs(lines)
+        );
+    }
+
+    #[test]
+    fn test_reduce_trie_input_order() {
+        let input = "2001:db8::1\n192.168.1.1\n10.0.0.1\n10.0.0.0/8\n172.16.0.0/12\n";
+
+        let (trie, _) = ReduceTrie::from_reader(input.as_bytes()).unwrap();
+
+        assert_eq!(
+            vec![
+                "2001:db8::1/128".parse::<IpNet>().unwrap(),
+                "192.168.1.1/32".parse().unwrap(),
+                "10.0.0.0/8".parse().unwrap(),
+                "172.16.0.0/12".parse().unwrap(),
+            ],
+            trie.get_sorted_prefixes(SortOrder::InputOrder)
+        );
+    }
+
+    #[test]
+    fn test_aggregate_keeps_first_occurrence() {
+        let input = "10.0.0.128/25\n192.168.0.0/16\n10.0.0.0/25\n";
+
+        let (mut trie, _) = ReduceTrie::from_reader(input.as_bytes()).unwrap();
+        trie.aggregate();
+
+        assert_eq!(
+            vec![
+                "10.0.0.0/24".parse::<IpNet>().unwrap(),
+                "192.168.0.0/16".parse().unwrap(),
+            ],
+            trie.get_sorted_prefixes(SortOrder::InputOrder)
+        );
+    }
 }
@@ -0,0 +1,266 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use ipnet::IpNet;
+
+/// An entry of the doubly linked list of prefixes, ordered by address within each IP family.
+struct Entry {
+    prefix: IpNet,
+    seq: usize,
+    prev: Option<usize>,
+    next: Option<usize>,
+    alive: bool,
+}
+
+/// A possible merge of two neighbouring prefixes into their smallest common supernet.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Candidate {
+    waste: u128,
+    left: usize,
+    right: usize,
+    left_prefix: IpNet,
+    right_prefix: IpNet,
+    supernet: IpNet,
+}
+
+/// Merges neighbouring prefixes until at most `max_entries` are left, always picking the merge
+/// adding the least addresses not covered before. Merges into a supernet for which `allowed`
+/// returns `false` are skipped, so more than `max_entries` prefixes may be left.
+///
+/// The prefixes of each family have to be sorted by address and must not overlap (which is the
+/// case for the prefixes of an aggregated `ReduceTrie`). Each prefix is accompanied by its
+/// sequence number, a merged prefix keeps the lower one. Returns the remaining prefixes and the
+/// number of addresses added by the merges.
+pub fn merge_cheapest(
+    prefixes: Vec<(IpNet, usize)>,
+    max_entries: usize,
+    allowed: impl Fn(&IpNet) -> bool,
+) -> (Vec<(IpNet, usize)>, u128) {
+    let mut entries: Vec<Entry> = Vec::with_capacity(prefixes.len());
+    for (idx, (prefix, seq)) in prefixes.into_iter().enumerate() {
+        let linked = idx > 0 && is_same_family(&entries[idx - 1].prefix, &prefix);
+        if linked {
+            entries[idx - 1].next = Some(idx);
+        }
+
+        entries.push(Entry {
+            prefix,
+            seq,
+            prev: linked.then(|| idx - 1),
+            next: None,
+            alive: true,
+        });
+    }
+
+    let mut heap = BinaryHeap::new();
+    for idx in 0..entries.len() {
+        push_candidate(&entries, &mut heap, Some(idx), &allowed);
+    }
+
+    let mut count = entries.len();
+    let mut added: u128 = 0;
+
+    while count > max_entries {
+        let Some(Reverse(candidate)) = heap.pop() else {
+            break;
+        };
+
+        if !is_current(&entries, &candidate) {
+            continue;
+        }
+
+        let next = entries[candidate.right].next;
+        let seq = entries[candidate.left]
+            .seq
+            .min(entries[candidate.right].seq);
+        entries[candidate.right].alive = false;
+        entries[candidate.left].prefix = candidate.supernet;
+        entries[candidate.left].seq = seq;
+        entries[candidate.left].next = next;
+        if let Some(next) = next {
+            entries[next].prev = Some(candidate.left);
+        }
+
+        count -= 1;
+        added = added.saturating_add(candidate.waste);
+
+        push_candidate(&entries, &mut heap, entries[candidate.left].prev, &allowed);
+        push_candidate(&entries, &mut heap, Some(candidate.left), &allowed);
+    }
+
+    let result = entries
+        .into_iter()
+        .filter(|e| e.alive)
+        .map(|e| (e.prefix, e.seq))
+        .collect();
+
+    (result, added)
+}
+
+fn push_candidate(
+    entries: &[Entry],
+    heap: &mut BinaryHeap<Reverse<Candidate>>,
+    left: Option<usize>,
+    allowed: &impl Fn(&IpNet) -> bool,
+) {
+    let Some(left) = left else {
+        return;
+    };
+    let Some(right) = entries[left].next else {
+        return;
+    };
+
+    let left_prefix = entries[left].prefix;
+    let right_prefix = entries[right].prefix;
+    let supernet = common_supernet(&left_prefix, &right_prefix);
+
+    // the supernet must not swallow any other prefix, otherwise a cheaper merge exists
+    let swallows_neighbour =
+        |idx: Option<usize>| idx.is_some_and(|idx| supernet.contains(&entries[idx].prefix));
+    if swallows_neighbour(entries[left].prev) || swallows_neighbour(entries[right].next) {
+        return;
+    }
+
+    if !allowed(&supernet) {
+        return;
+    }
+
+    let waste = address_count(&supernet)
+        .saturating_sub(address_count(&left_prefix))
+        .saturating_sub(address_count(&right_prefix));
+
+    heap.push(Reverse(Candidate {
+        waste,
+        left,
+        right,
+        left_prefix,
+        right_prefix,
+        supernet,
+    }));
+}
+
+fn is_current(entries: &[Entry], candidate: &Candidate) -> bool {
+    let left = &entries[candidate.left];
+    let right = &entries[candidate.right];
+
+    left.alive
+        && right.alive
+        && left.next == Some(candidate.right)
+        && left.prefix == candidate.left_prefix
+        && right.prefix == candidate.right_prefix
+}
+
+fn is_same_family(a: &IpNet, b: &IpNet) -> bool {
+    matches!(
+        (a, b),
+        (IpNet::V4(_), IpNet::V4(_)) | (IpNet::V6(_), IpNet::V6(_))
+    )
+}
+
+/// Returns the smallest prefix containing both given prefixes.
+fn common_supernet(a: &IpNet, b: &IpNet) -> IpNet {
+    let common_len = match (a, b) {
+        (IpNet::V4(a), IpNet::V4(b)) => {
+            (u32::from(a.addr()) ^ u32::from(b.addr())).leading_zeros() as u8
+        }
+        (IpNet::V6(a), IpNet::V6(b)) => {
+            (u128::from(a.addr()) ^ u128::from(b.addr())).leading_zeros() as u8
+        }
+        _ => unreachable!("prefixes of different families can not be merged"),
+    };
+    let len = common_len.min(a.prefix_len()).min(b.prefix_len());
+
+    IpNet::new(a.addr(), len)
+        .expect("prefix length is bounded by the input prefixes")
+        .trunc()
+}
+
+/// Returns the number of addresses covered by the prefix (saturated for ::/0).
+fn address_count(prefix: &IpNet) -> u128 {
+    let host_bits = u32::from(prefix.max_prefix_len() - prefix.prefix_len());
+    1u128.checked_shl(host_bits).unwrap_or(u128::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nets(prefixes: &[&str]) -> Vec<(IpNet, usize)> {
+        prefixes
+            .iter()
+            .enumerate()
+            .map(|(seq, p)| (p.parse().unwrap(), seq))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_cheapest_picks_least_waste() {
+        let prefixes = nets(&["10.0.0.0/24", "10.0.2.0/24", "10.0.8.0/24"]);
+
+        let (result, added) = merge_cheapest(prefixes, 2, |_| true);
+
+        assert_eq!(
+            vec![
+                ("10.0.0.0/22".parse().unwrap(), 0),
+                ("10.0.8.0/24".parse().unwrap(), 2),
+            ],
+            result
+        );
+        assert_eq!(512, added);
+    }
+
+    #[test]
+    fn test_merge_cheapest_continues_with_merged_prefix() {
+        let prefixes = nets(&["10.0.0.0/24", "10.0.2.0/24", "10.0.4.0/23", "10.0.8.0/24"]);
+
+        let (result, added) = merge_cheapest(prefixes, 2, |_| true);
+
+        assert_eq!(
+            vec![
+                ("10.0.0.0/21".parse().unwrap(), 0),
+                ("10.0.8.0/24".parse().unwrap(), 3),
+            ],
+            result
+        );
+        assert_eq!(1024, added);
+    }
+
+    #[test]
+    fn test_merge_cheapest_keeps_families_apart() {
+        let prefixes = nets(&["10.0.0.0/24", "2001:db8::/48", "2001:db8:2::/48"]);
+
+        let (result, added) = merge_cheapest(prefixes, 1, |_| true);
+
+        assert_eq!(
+            vec![
+                ("10.0.0.0/24".parse().unwrap(), 0),
+                ("2001:db8::/46".parse().unwrap(), 1),
+            ],
+            result
+        );
+        assert_eq!(2 * (1u128 << 80), added);
+    }
+
+    #[test]
+    fn test_merge_cheapest_target_already_met() {
+        let prefixes = nets(&["10.0.0.0/24", "10.0.2.0/24"]);
+
+        let (result, added) = merge_cheapest(prefixes.clone(), 2, |_| true);
+
+        assert_eq!(prefixes, result);
+        assert_eq!(0, added);
+    }
+
+    #[test]
+    fn test_merge_cheapest_skips_disallowed_supernets() {
+        let prefixes = nets(&["10.0.0.0/25", "10.0.1.0/25", "10.0.4.0/24"]);
+        let excluded: IpNet = "10.0.0.128/25".parse().unwrap();
+
+        let (result, added) = merge_cheapest(prefixes, 1, |supernet| {
+            !supernet.contains(&excluded) && !excluded.contains(supernet)
+        });
+
+        assert_eq!(nets(&["10.0.0.0/25", "10.0.1.0/25", "10.0.4.0/24"]), result);
+        assert_eq!(0, added);
+    }
+}
@@ -1,31 +1,164 @@
 mod cli;
+mod firewall;
+mod firewall_input;
 mod input;
+mod input_format;
 mod output_format;
+mod router;
+mod selector;
+mod stats_format;
+mod template;
 
+use std::io::BufRead;
 use std::process::ExitCode;
 
-use crate::cli::Args;
-use net_reduce::reduce_cidrs;
+use crate::cli::{Args, Command};
+use crate::output_format::{Metadata, Summary};
+use net_reduce::{ParseReport, Provenance, Recorded, ReduceTrie};
 
 use anyhow::Result;
 use clap::Parser;
 
 fn main() -> ExitCode {
     let args = Args::parse();
-    let output_format = args.output_format;
 
-    let lines: Vec<String> = match read_input(args) {
-        Ok(lines) => lines,
-        Err(e) => {
+    if !check_stdin(&args) {
+        return ExitCode::FAILURE;
+    }
+
+    let mut provenance = Provenance::recording(Recorded {
+        sources: args.explain || args.with_source,
+        line_numbers: args.explain,
+        annotations: args.explain || args.with_annotations,
+    });
+    let recorded =
+        (args.explain || args.with_source || args.with_annotations || args.with_modifiers)
+            .then_some(&mut provenance);
+
+    let loaded = match &args.command {
+        None if args.inputs().is_empty() => load(&args, None, recorded),
+        None => load_all(&args, &args.inputs(), recorded)
+            .map(|(tries, report)| (combine(tries, ReduceTrie::union), report)),
+        Some(Command::Intersect { files }) => load_all(&args, files, recorded)
+            .map(|(tries, report)| (combine(tries, ReduceTrie::intersect), report)),
+        Some(Command::Union { files }) => load_all(&args, files, recorded)
+            .map(|(tries, report)| (combine(tries, ReduceTrie::union), report)),
+        Some(Command::Diff { old, new }) => return diff(&args, old, new),
+    };
+
+    let Some((mut trie, report)) = loaded else {
+        return ExitCode::FAILURE;
+    };
+
+    let Some(excluded) = exclude(&args, &mut [&mut trie]) else {
+        return ExitCode::FAILURE;
+    };
+
+    if let Some(max_entries) = args.max_entries {
+        let added = trie.aggregate_lossy_excluding(max_entries, &excluded);
+        eprintln!("{added} addresses covered in addition to the input");
+
+        let prefixes = trie.get_all_prefixes();
+        if prefixes.len() > max_entries {
+            let entries = prefixes.len();
+            let ipv4 = prefixes.iter().any(|p| p.addr().is_ipv4());
+            let ipv6 = prefixes.iter().any(|p| p.addr().is_ipv6());
+            let families = usize::from(ipv4) + usize::from(ipv6);
+
+            if args.exclude.is_some() && entries > max_entries.max(families) {
+                eprintln!("{entries} entries left, merging further would cover excluded prefixes");
+            } else {
+                eprintln!("{entries} entries left, each IP family keeps at least one prefix");
+            }
+        }
+    } else if args.aggregate || args.command.is_some() {
+        trie.aggregate();
+    }
+
+    let metadata = Metadata {
+        sources: args.with_source,
+        annotations: args.with_annotations,
+        modifiers: args.with_modifiers,
+        summary: args.structured.then_some(Summary {
+            inputs: report.prefixes,
+            invalid: report.invalid.len(),
+        }),
+    };
+
+    let w = std::io::stdout();
+    let result = if args.explain {
+        let explanations = provenance.explain(&trie, args.sort);
+        args.output_format().write_explanations(explanations, w)
+    } else if args.with_source || args.with_annotations || args.with_modifiers || args.structured {
+        let explanations = provenance.explain(&trie, args.sort);
+        args.output_format()
+            .write_with_metadata(explanations, metadata, w)
+    } else {
+        let reduced = trie
+            .get_sorted_prefixes(args.sort)
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        args.output_format().write(reduced, w)
+    };
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(format) = args.stats {
+        let outputs = trie.get_all_prefixes();
+        if let Err(e) = format.write(
+            trie.stats(),
+            report.invalid.len(),
+            &outputs,
+            std::io::stderr(),
+        ) {
             eprintln!("{e}");
             return ExitCode::FAILURE;
         }
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn diff(args: &Args, old: &str, new: &str) -> ExitCode {
+    let unsupported = [
+        ("--max-entries", args.max_entries.is_some()),
+        ("--explain", args.explain),
+        ("--stats", args.stats.is_some()),
+        ("--structured", args.structured),
+    ];
+    if let Some((flag, _)) = unsupported.iter().find(|(_, set)| *set) {
+        eprintln!("{flag} is not supported by diff");
+        return ExitCode::FAILURE;
+    }
+
+    let (Some((mut old, _)), Some((mut new, _))) =
+        (load(args, Some(old), None), load(args, Some(new), None))
+    else {
+        return ExitCode::FAILURE;
     };
 
-    let reduced = reduce_cidrs(lines);
+    if exclude(args, &mut [&mut old, &mut new]).is_none() {
+        return ExitCode::FAILURE;
+    }
+
+    let (added, removed) = old.diff(&new);
+
+    let to_strings = |trie: ReduceTrie| {
+        trie.get_sorted_prefixes(args.sort)
+            .iter()
+            .map(|p| p.to_string())
+            .collect()
+    };
 
     let w = std::io::stdout();
-    if let Err(e) = output_format.write(reduced, w) {
+    if let Err(e) = args
+        .output_format()
+        .write_diff(to_strings(added), to_strings(removed), w)
+    {
         eprintln!("{e}");
         return ExitCode::FAILURE;
     }
@@ -33,9 +166,178 @@ fn main() -> ExitCode {
     ExitCode::SUCCESS
 }
 
-fn read_input(args: Args) -> Result<Vec<String>> {
-    match args.file {
-        Some(file) => input::from_file(&file),
-        None => input::from_stdin(),
+/// Returns `false` if stdin is given more than once (as input, file of a subcommand or exclude
+/// file), since it can only be read once.
+fn check_stdin(args: &Args) -> bool {
+    let inputs = args.inputs();
+    let mut files: Vec<&str> = match &args.command {
+        None if inputs.is_empty() => vec!["-"],
+        None => inputs.iter().map(String::as_str).collect(),
+        Some(Command::Intersect { files } | Command::Union { files }) => {
+            files.iter().map(String::as_str).collect()
+        }
+        Some(Command::Diff { old, new }) => vec![old, new],
+    };
+    files.extend(args.exclude.as_deref());
+
+    if files.iter().filter(|file| **file == "-").count() > 1 {
+        eprintln!("stdin (-) can only be read once");
+        return false;
     }
+
+    true
+}
+
+/// Removes the prefixes of the exclude file (if any) from all tries. Returns the excluded prefixes
+/// (empty without exclude file) or `None` if processing should be aborted.
+fn exclude(args: &Args, tries: &mut [&mut ReduceTrie]) -> Option<ReduceTrie> {
+    let Some(file) = &args.exclude else {
+        return Some(ReduceTrie::new());
+    };
+
+    let (excluded, _) = checked(args, Some(file), read_exclude(args, file))?;
+
+    for trie in tries {
+        trie.exclude(&excluded);
+    }
+
+    Some(excluded)
+}
+
+/// Reads and parses the input, errors and invalid lines are printed to stderr. The origin of each
+/// prefix is recorded in `provenance` if given. Returns `None` if processing should be aborted.
+fn load(
+    args: &Args,
+    file: Option<&str>,
+    provenance: Option<&mut Provenance>,
+) -> Option<(ReduceTrie, ParseReport)> {
+    checked(args, file, read_input(args, file, provenance))
+}
+
+/// Prints the error or the invalid lines of the parsed input to stderr. Returns `None` if
+/// processing should be aborted.
+fn checked(
+    args: &Args,
+    file: Option<&str>,
+    result: Result<(ReduceTrie, ParseReport)>,
+) -> Option<(ReduceTrie, ParseReport)> {
+    let (trie, report) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            match file {
+                Some(file) => eprintln!("{file}: {e}"),
+                None => eprintln!("{e}"),
+            }
+            return None;
+        }
+    };
+
+    if !check_report(args, &report, file) {
+        return None;
+    }
+
+    Some((trie, report))
+}
+
+/// Loads each file separately, wildcards in the file names are expanded first. The reports of
+/// all files are merged.
+fn load_all(
+    args: &Args,
+    files: &[String],
+    mut provenance: Option<&mut Provenance>,
+) -> Option<(Vec<ReduceTrie>, ParseReport)> {
+    let mut tries = Vec::new();
+    let mut merged = ParseReport::default();
+
+    for pattern in files {
+        let paths = match input::expand_glob(pattern) {
+            Ok(paths) => paths,
+            Err(e) => {
+                eprintln!("{e}");
+                return None;
+            }
+        };
+
+        for path in paths {
+            let (trie, report) = load(args, Some(&path), provenance.as_deref_mut())?;
+            tries.push(trie);
+            merged.merge(report);
+        }
+    }
+
+    Some((tries, merged))
+}
+
+/// Combines all tries into the first one using the given set operation.
+fn combine(tries: Vec<ReduceTrie>, op: fn(&mut ReduceTrie, &ReduceTrie)) -> ReduceTrie {
+    let mut tries = tries.into_iter();
+    let mut result = tries.next().expect("at least one file is required");
+
+    for trie in tries {
+        op(&mut result, &trie);
+    }
+
+    result
+}
+
+/// Prints the invalid lines if requested, returns `false` if processing should be aborted.
+fn check_report(args: &Args, report: &ParseReport, source: Option<&str>) -> bool {
+    let prefix = source.map(|s| format!("{s}: ")).unwrap_or_default();
+
+    if args.report_invalid || args.strict {
+        for invalid in &report.invalid {
+            eprintln!("{prefix}{invalid}");
+        }
+    }
+
+    if args.strict && !report.is_empty() {
+        eprintln!("{prefix}{} invalid lines found", report.invalid.len());
+        return false;
+    }
+
+    true
+}
+
+fn read_input(
+    args: &Args,
+    file: Option<&str>,
+    provenance: Option<&mut Provenance>,
+) -> Result<(ReduceTrie, ParseReport)> {
+    let mut reader: Box<dyn BufRead> = match file {
+        Some("-") | None => input::from_stdin(),
+        Some(file) => input::from_file(file)?,
+    };
+
+    reader = args
+        .input_format
+        .lines(reader, args.column.as_ref(), args.select.as_ref())?;
+
+    if let Some(regex) = &args.extract_regex {
+        reader = input::extract_regex(reader, regex.clone());
+    }
+
+    let options = args.parse_options();
+
+    let result = match provenance {
+        Some(provenance) => {
+            ReduceTrie::from_reader_with_provenance(reader, options, file, provenance)?
+        }
+        None => ReduceTrie::from_reader_with_options(reader, options)?,
+    };
+
+    Ok(result)
+}
+
+/// Reads the exclude file, which is always a list of prefixes. The input format and extraction of
+/// the addresses (`--extract`, `--extract-regex`) only apply to the input.
+fn read_exclude(args: &Args, file: &str) -> Result<(ReduceTrie, ParseReport)> {
+    let reader: Box<dyn BufRead> = match file {
+        "-" => input::from_stdin(),
+        file => input::from_file(file)?,
+    };
+
+    Ok(ReduceTrie::from_reader_with_options(
+        reader,
+        args.exclude_options(),
+    )?)
 }
@@ -1,13 +1,63 @@
+use std::collections::BTreeMap;
 use std::io::Write;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use ipnet::IpNet;
+use net_reduce::Explanation;
+use serde_json::{Value, json};
+
+use crate::firewall::{self, FirewallNames};
+use crate::router::{self, Entry, RouterOptions};
+use crate::template::Template;
+
+/// Metadata of the input lines which can be added to each prefix of the output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Metadata {
+    /// Files the prefix was read from
+    pub sources: bool,
+    /// Trailing comments of the input lines
+    pub annotations: bool,
+    /// `le`/`ge` modifiers of router prefix lists matching the more specific input prefixes
+    pub modifiers: bool,
+    /// Summary of the input for structured JSON/YAML output, which adds details of each prefix
+    pub summary: Option<Summary>,
+}
+
+/// Counts of the input written in the summary header of structured output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Summary {
+    /// Number of prefixes read from the input
+    pub inputs: usize,
+    /// Number of invalid lines
+    pub invalid: usize,
+}
 
 /// Output format specifies the formating which will be used when writing to output
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum OutputFormat {
     Json,
     Yaml,
     List,
+    /// nftables table with an interval set per family
+    Nftables(FirewallNames),
+    /// `ipset restore` script with a set per family
+    Ipset(FirewallNames),
+    /// `iptables-restore` rules for the IPv4 prefixes
+    Iptables(FirewallNames),
+    /// `ip6tables-restore` rules for the IPv6 prefixes
+    Ip6tables(FirewallNames),
+    /// pf table file
+    Pf(FirewallNames),
+    /// Cisco IOS `ip prefix-list` per family
+    Cisco(RouterOptions),
+    /// Junos `policy-options prefix-list` per family
+    Juniper(RouterOptions),
+    /// BIRD prefix set per family
+    Bird(RouterOptions),
+    /// FRR `ip prefix-list` per family
+    Frr(RouterOptions),
+    /// User-defined template
+    Template(Template),
 }
 
 impl OutputFormat {
@@ -17,9 +67,209 @@ impl OutputFormat {
             OutputFormat::Json => self.write_json(prefixes, w),
             OutputFormat::Yaml => self.write_yaml(prefixes, w),
             OutputFormat::List => self.write_list(prefixes, w),
+            OutputFormat::Nftables(names) => firewall::write_nftables(&prefixes, names, w),
+            OutputFormat::Ipset(names) => firewall::write_ipset(&prefixes, names, w),
+            OutputFormat::Iptables(names) => firewall::write_iptables(&prefixes, names, false, w),
+            OutputFormat::Ip6tables(names) => firewall::write_iptables(&prefixes, names, true, w),
+            OutputFormat::Pf(names) => firewall::write_pf(&prefixes, names, w),
+            OutputFormat::Cisco(_)
+            | OutputFormat::Juniper(_)
+            | OutputFormat::Bird(_)
+            | OutputFormat::Frr(_) => self.write_entries(&Entry::from_strings(&prefixes)?, w),
+            OutputFormat::Template(template) => template.write(&prefixes, w),
+        }
+    }
+
+    /// Writes prefix list entries to writer using specified output format, other formats than
+    /// the router formats ignore the modifiers.
+    fn write_entries<W: Write>(&self, entries: &[Entry], w: W) -> Result<()> {
+        match self {
+            OutputFormat::Cisco(options) | OutputFormat::Frr(options) => {
+                router::write_cisco(entries, options, w)
+            }
+            OutputFormat::Juniper(options) => router::write_juniper(entries, options, w),
+            OutputFormat::Bird(options) => router::write_bird(entries, options, w),
+            _ => self.write(entries.iter().map(|e| e.prefix.to_string()).collect(), w),
+        }
+    }
+
+    /// Returns the output format with the given names of the firewall sets and options of the
+    /// router prefix lists, other formats are returned unchanged.
+    pub fn configure(self, names: FirewallNames, options: RouterOptions) -> Self {
+        match self {
+            OutputFormat::Nftables(_) => OutputFormat::Nftables(names),
+            OutputFormat::Ipset(_) => OutputFormat::Ipset(names),
+            OutputFormat::Iptables(_) => OutputFormat::Iptables(names),
+            OutputFormat::Ip6tables(_) => OutputFormat::Ip6tables(names),
+            OutputFormat::Pf(_) => OutputFormat::Pf(names),
+            OutputFormat::Cisco(_) => OutputFormat::Cisco(options),
+            OutputFormat::Juniper(_) => OutputFormat::Juniper(options),
+            OutputFormat::Bird(_) => OutputFormat::Bird(options),
+            OutputFormat::Frr(_) => OutputFormat::Frr(options),
+            format => format,
         }
     }
 
+    /// Returns `true` for formats written by `write_value`.
+    fn is_structured(&self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Yaml)
+    }
+
+    /// Writes the prefixes added and removed between two prefix lists to writer using specified
+    /// output format. JSON and YAML output is an object with the keys `added` and `removed`, list
+    /// output prefixes each line with `+` or `-`.
+    pub fn write_diff<W: Write>(
+        &self,
+        added: Vec<String>,
+        removed: Vec<String>,
+        mut w: W,
+    ) -> Result<()> {
+        let diff = BTreeMap::from([("added", added), ("removed", removed)]);
+
+        match self {
+            OutputFormat::Json => {
+                let json = serde_json::to_string(&diff)
+                    .with_context(|| "failed to serialize diff to JSON")?;
+                writeln!(w, "{json}").with_context(|| "failed to write output")?;
+            }
+            OutputFormat::Yaml => {
+                let yaml = serde_yaml::to_string(&diff)
+                    .with_context(|| "failed to serialize diff to YAML")?;
+                writeln!(w, "{yaml}").with_context(|| "failed to write output")?;
+            }
+            OutputFormat::List => {
+                for prefix in &diff["added"] {
+                    writeln!(w, "+{prefix}")?;
+                }
+                for prefix in &diff["removed"] {
+                    writeln!(w, "-{prefix}")?;
+                }
+            }
+            _ => bail!("diff output is only supported by the json, yaml and list formats"),
+        }
+
+        Ok(())
+    }
+
+    /// Writes the prefixes together with the input lines they account for to writer using
+    /// specified output format. List output prints each input line indented below its prefix,
+    /// firewall formats only contain the prefixes.
+    pub fn write_explanations<W: Write>(
+        &self,
+        explanations: Vec<Explanation>,
+        mut w: W,
+    ) -> Result<()> {
+        if let OutputFormat::List = self {
+            for explanation in explanations {
+                writeln!(w, "{}", explanation.prefix)?;
+                for (prefix, origin) in explanation.inputs {
+                    match &origin.annotation {
+                        Some(annotation) => writeln!(w, "  {prefix} ({origin}) # {annotation}")?,
+                        None => writeln!(w, "  {prefix} ({origin})")?,
+                    }
+                }
+            }
+
+            return Ok(());
+        }
+
+        if !self.is_structured() {
+            return self.write(prefixes(&explanations), w);
+        }
+
+        let value: Vec<Value> = explanations.iter().map(explanation_to_value).collect();
+        self.write_value(&Value::from(value), w)
+    }
+
+    /// Writes the prefixes together with the requested metadata of the input lines they account
+    /// for to writer using specified output format. Sources and annotations are listed once each,
+    /// in input order (`-` for stdin). List output appends the annotations as trailing comment,
+    /// sources are omitted. Firewall and router formats only contain the prefixes, router prefix
+    /// lists with `le`/`ge` modifiers if requested. With a summary JSON and YAML output is an
+    /// object with the keys `summary` and `prefixes`, each prefix with its details.
+    pub fn write_with_metadata<W: Write>(
+        &self,
+        explanations: Vec<Explanation>,
+        metadata: Metadata,
+        mut w: W,
+    ) -> Result<()> {
+        if let OutputFormat::List = self {
+            for explanation in &explanations {
+                let annotations = annotations(explanation);
+                if metadata.annotations && !annotations.is_empty() {
+                    writeln!(w, "{} # {}", explanation.prefix, annotations.join(", "))?;
+                } else {
+                    writeln!(w, "{}", explanation.prefix)?;
+                }
+            }
+
+            return Ok(());
+        }
+
+        if !self.is_structured() && metadata.modifiers {
+            let entries: Vec<Entry> = explanations.iter().map(Entry::from_explanation).collect();
+            return self.write_entries(&entries, w);
+        } else if !self.is_structured() {
+            return self.write(prefixes(&explanations), w);
+        }
+
+        let value: Vec<Value> = explanations
+            .iter()
+            .map(|e| {
+                let mut value = json!({"prefix": e.prefix.to_string()});
+                if metadata.sources {
+                    value["sources"] = json!(sources(e));
+                }
+                if metadata.annotations {
+                    value["annotations"] = json!(annotations(e));
+                }
+                if metadata.modifiers {
+                    let entry = Entry::from_explanation(e);
+                    value["ge"] = json!(entry.ge);
+                    value["le"] = json!(entry.le);
+                }
+                if metadata.summary.is_some() {
+                    add_details(&mut value, &e.prefix);
+                }
+
+                value
+            })
+            .collect();
+
+        match metadata.summary {
+            Some(summary) => {
+                let document = json!({
+                    "summary": {
+                        "inputs": summary.inputs,
+                        "outputs": value.len(),
+                        "invalid": summary.invalid,
+                    },
+                    "prefixes": value,
+                });
+                self.write_value(&document, w)
+            }
+            None => self.write_value(&Value::from(value), w),
+        }
+    }
+
+    fn write_value<W: Write>(&self, value: &Value, mut w: W) -> Result<()> {
+        match self {
+            OutputFormat::Json => {
+                let json = serde_json::to_string(value)
+                    .with_context(|| "failed to serialize prefixes to JSON")?;
+                writeln!(w, "{json}").with_context(|| "failed to write output")?;
+            }
+            OutputFormat::Yaml => {
+                let yaml = serde_yaml::to_string(value)
+                    .with_context(|| "failed to serialize prefixes to YAML")?;
+                writeln!(w, "{yaml}").with_context(|| "failed to write output")?;
+            }
+            _ => unreachable!("only JSON and YAML are written as value"),
+        }
+
+        Ok(())
+    }
+
     fn write_json<W: Write>(&self, prefixes: Vec<String>, mut w: W) -> Result<()> {
         let json = serde_json::to_string(&prefixes)
             .with_context(|| "failed to serialize prefixes to JSON")?;
@@ -45,6 +295,99 @@ impl OutputFormat {
     }
 }
 
+/// Returns the number of addresses covered by the non overlapping prefixes as string, since it
+/// exceeds 64 bits for IPv6 prefixes shorter than /65.
+pub fn address_count(prefixes: &[IpNet]) -> String {
+    let count = prefixes.iter().try_fold(0u128, |count, prefix| {
+        let host_bits = u32::from(prefix.max_prefix_len() - prefix.prefix_len());
+        count.checked_add(1u128.checked_shl(host_bits)?)
+    });
+
+    match count {
+        Some(count) => count.to_string(),
+        // only the whole IPv6 address space (2^128) does not fit
+        None => "340282366920938463463374607431768211456".to_string(),
+    }
+}
+
+/// Adds the details of the prefix to the JSON object.
+fn add_details(value: &mut Value, prefix: &IpNet) {
+    let addresses = address_count(std::slice::from_ref(prefix));
+
+    value["family"] = json!(match prefix {
+        IpNet::V4(_) => "ipv4",
+        IpNet::V6(_) => "ipv6",
+    });
+    value["network"] = json!(prefix.network().to_string());
+    value["prefix_len"] = json!(prefix.prefix_len());
+    value["netmask"] = json!(prefix.netmask().to_string());
+    value["first"] = json!(prefix.network().to_string());
+    value["last"] = json!(prefix.broadcast().to_string());
+    value["addresses"] = json!(addresses);
+}
+
+fn prefixes(explanations: &[Explanation]) -> Vec<String> {
+    explanations.iter().map(|e| e.prefix.to_string()).collect()
+}
+
+/// Returns the distinct sources of the input lines in order of their first occurrence.
+fn sources(explanation: &Explanation) -> Vec<&str> {
+    distinct(
+        explanation
+            .inputs
+            .iter()
+            .map(|(_, origin)| origin.source.as_deref().unwrap_or("-")),
+    )
+}
+
+/// Returns the distinct annotations of the input lines in order of their first occurrence.
+fn annotations(explanation: &Explanation) -> Vec<&str> {
+    distinct(
+        explanation
+            .inputs
+            .iter()
+            .filter_map(|(_, origin)| origin.annotation.as_deref()),
+    )
+}
+
+fn distinct<'a>(values: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut result: Vec<&str> = Vec::new();
+
+    for value in values {
+        if !result.contains(&value) {
+            result.push(value);
+        }
+    }
+
+    result
+}
+
+fn explanation_to_value(explanation: &Explanation) -> Value {
+    let inputs: Vec<Value> = explanation
+        .inputs
+        .iter()
+        .map(|(prefix, origin)| {
+            let mut input = json!({
+                "prefix": prefix.to_string(),
+                "line": origin.line_number,
+            });
+            if let Some(source) = &origin.source {
+                input["source"] = json!(&**source);
+            }
+            if let Some(annotation) = &origin.annotation {
+                input["annotation"] = json!(&**annotation);
+            }
+
+            input
+        })
+        .collect();
+
+    json!({
+        "prefix": explanation.prefix.to_string(),
+        "inputs": inputs,
+    })
+}
+
 impl std::str::FromStr for OutputFormat {
     type Err = anyhow::Error;
 
@@ -53,6 +396,15 @@ impl std::str::FromStr for OutputFormat {
             "json" => Ok(OutputFormat::Json),
             "yaml" => Ok(OutputFormat::Yaml),
             "list" => Ok(OutputFormat::List),
+            "nftables" | "nft" => Ok(OutputFormat::Nftables(FirewallNames::default())),
+            "ipset" => Ok(OutputFormat::Ipset(FirewallNames::default())),
+            "iptables" => Ok(OutputFormat::Iptables(FirewallNames::default())),
+            "ip6tables" => Ok(OutputFormat::Ip6tables(FirewallNames::default())),
+            "pf" => Ok(OutputFormat::Pf(FirewallNames::default())),
+            "cisco" => Ok(OutputFormat::Cisco(RouterOptions::default())),
+            "juniper" => Ok(OutputFormat::Juniper(RouterOptions::default())),
+            "bird" => Ok(OutputFormat::Bird(RouterOptions::default())),
+            "frr" => Ok(OutputFormat::Frr(RouterOptions::default())),
             _ => Err(anyhow::anyhow!("Unknown output format: {}", s)),
         }
     }
@@ -61,6 +413,7 @@ impl std::str::FromStr for OutputFormat {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use net_reduce::Origin;
     use std::str::FromStr;
 
     #[test]
@@ -219,4 +572,202 @@ mod tests {
         let output = String::from_utf8(buffer).unwrap();
         assert_eq!(output, "");
     }
+
+    #[test]
+    fn test_write_diff_json() {
+        let format = OutputFormat::Json;
+        let mut buffer = Vec::new();
+
+        let result = format.write_diff(
+            vec!["10.0.1.0/24".to_string()],
+            vec!["192.168.1.1/32".to_string(), "2001:db8::/64".to_string()],
+            &mut buffer,
+        );
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output.trim(),
+            r#"{"added":["10.0.1.0/24"],"removed":["192.168.1.1/32","2001:db8::/64"]}"#
+        );
+    }
+
+    #[test]
+    fn test_write_diff_yaml() {
+        let format = OutputFormat::Yaml;
+        let mut buffer = Vec::new();
+
+        let result = format.write_diff(vec!["10.0.1.0/24".to_string()], vec![], &mut buffer);
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(output.trim(), "added:\n- 10.0.1.0/24\nremoved: []");
+    }
+
+    #[test]
+    fn test_write_diff_list() {
+        let format = OutputFormat::List;
+        let mut buffer = Vec::new();
+
+        let result = format.write_diff(
+            vec!["10.0.1.0/24".to_string()],
+            vec!["192.168.1.1/32".to_string()],
+            &mut buffer,
+        );
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(output, "+10.0.1.0/24\n-192.168.1.1/32\n");
+    }
+
+    fn explanations() -> Vec<Explanation> {
+        vec![Explanation {
+            prefix: "10.0.0.0/8".parse().unwrap(),
+            inputs: vec![
+                (
+                    "10.0.0.0/8".parse().unwrap(),
+                    Origin {
+                        source: Some("a.txt".into()),
+                        line_number: 1,
+                        annotation: Some("RFC1918".into()),
+                    },
+                ),
+                (
+                    "10.1.0.0/16".parse().unwrap(),
+                    Origin {
+                        source: None,
+                        line_number: 4,
+                        annotation: None,
+                    },
+                ),
+            ],
+        }]
+    }
+
+    #[test]
+    fn test_write_explanations_json() {
+        let format = OutputFormat::Json;
+        let mut buffer = Vec::new();
+
+        let result = format.write_explanations(explanations(), &mut buffer);
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output.trim(),
+            r#"[{"inputs":[{"annotation":"RFC1918","line":1,"prefix":"10.0.0.0/8","source":"a.txt"},{"line":4,"prefix":"10.1.0.0/16"}],"prefix":"10.0.0.0/8"}]"#
+        );
+    }
+
+    #[test]
+    fn test_write_explanations_list() {
+        let format = OutputFormat::List;
+        let mut buffer = Vec::new();
+
+        let result = format.write_explanations(explanations(), &mut buffer);
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            "10.0.0.0/8\n  10.0.0.0/8 (a.txt:1) # RFC1918\n  10.1.0.0/16 (line 4)\n"
+        );
+    }
+
+    #[test]
+    fn test_write_with_metadata_yaml() {
+        let format = OutputFormat::Yaml;
+        let mut buffer = Vec::new();
+        let metadata = Metadata {
+            sources: true,
+            annotations: true,
+            ..Default::default()
+        };
+
+        let result = format.write_with_metadata(explanations(), metadata, &mut buffer);
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output.trim(),
+            "- annotations:\n  - RFC1918\n  prefix: 10.0.0.0/8\n  sources:\n  - a.txt\n  - '-'"
+        );
+    }
+
+    #[test]
+    fn test_write_with_metadata_list() {
+        let format = OutputFormat::List;
+        let mut buffer = Vec::new();
+        let metadata = Metadata {
+            sources: true,
+            annotations: true,
+            ..Default::default()
+        };
+
+        let result = format.write_with_metadata(explanations(), metadata, &mut buffer);
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(output, "10.0.0.0/8 # RFC1918\n");
+    }
+
+    #[test]
+    fn test_write_with_metadata_modifiers() {
+        let format = OutputFormat::Cisco(RouterOptions::default());
+        let mut buffer = Vec::new();
+        let metadata = Metadata {
+            modifiers: true,
+            ..Default::default()
+        };
+
+        let result = format.write_with_metadata(explanations(), metadata, &mut buffer);
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            "no ip prefix-list net_reduce_v4
+ip prefix-list net_reduce_v4 seq 5 permit 10.0.0.0/8 le 16
+"
+        );
+    }
+
+    #[test]
+    fn test_write_with_metadata_structured() {
+        let format = OutputFormat::Json;
+        let mut buffer = Vec::new();
+        let metadata = Metadata {
+            summary: Some(Summary {
+                inputs: 3,
+                invalid: 1,
+            }),
+            ..Default::default()
+        };
+
+        let result = format.write_with_metadata(explanations(), metadata, &mut buffer);
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output.trim(),
+            r#"{"prefixes":[{"addresses":"16777216","family":"ipv4","first":"10.0.0.0","last":"10.255.255.255","netmask":"255.0.0.0","network":"10.0.0.0","prefix":"10.0.0.0/8","prefix_len":8}],"summary":{"inputs":3,"invalid":1,"outputs":1}}"#
+        );
+    }
+
+    #[test]
+    fn test_add_details_ipv6() {
+        let mut value = json!({});
+        add_details(&mut value, &"2001:db8::/64".parse().unwrap());
+
+        assert_eq!("ipv6", value["family"]);
+        assert_eq!("2001:db8::ffff:ffff:ffff:ffff", value["last"]);
+        assert_eq!("ffff:ffff:ffff:ffff::", value["netmask"]);
+        assert_eq!("18446744073709551616", value["addresses"]);
+
+        add_details(&mut value, &"::/0".parse().unwrap());
+        assert_eq!(
+            "340282366920938463463374607431768211456",
+            value["addresses"]
+        );
+    }
 }
@@ -0,0 +1,330 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use ipnet::IpNet;
+
+use crate::reduce_trie::ReduceTrie;
+use crate::sort_order::SortOrder;
+
+/// Location of a prefix in the input. The source is shared by all prefixes of the same input.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Origin {
+    /// Name of the input (e.g. the file name), `None` for unnamed input like stdin
+    pub source: Option<Arc<str>>,
+    /// Line number in the input, starting at 1 (0 if line numbers are not recorded)
+    pub line_number: usize,
+    /// Text of the trailing comment of the line (e.g. `RFC1918` for `10.0.0.0/8 # RFC1918`)
+    pub annotation: Option<Arc<str>>,
+}
+
+/// Parts of the origin recorded by a `Provenance`, the others are dropped to save memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recorded {
+    /// Name of the input
+    pub sources: bool,
+    /// Line number in the input, without line numbers identical records are stored only once
+    pub line_numbers: bool,
+    /// Trailing comment of the line
+    pub annotations: bool,
+}
+
+impl Default for Recorded {
+    fn default() -> Self {
+        Self {
+            sources: true,
+            line_numbers: true,
+            annotations: true,
+        }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{source}:{}", self.line_number),
+            None => write!(f, "line {}", self.line_number),
+        }
+    }
+}
+
+/// A prefix left after reduction together with the input prefixes it accounts for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub prefix: IpNet,
+    /// Input prefixes contained in the prefix (or containing it, if the input prefix was split),
+    /// in input order
+    pub inputs: Vec<(IpNet, Origin)>,
+}
+
+/// Records where each prefix of the input came from, so the prefixes left after reduction can be
+/// traced back to the input lines they absorbed.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::{ParseOptions, Provenance, ReduceTrie, SortOrder};
+///
+/// let input = "10.0.0.0/8\n10.1.0.0/16\n192.168.1.1\n";
+/// let mut provenance = Provenance::new();
+/// let options = ParseOptions::default();
+/// let (trie, _) = ReduceTrie::from_reader_with_provenance(
+///     input.as_bytes(),
+///     options,
+///     Some("feed.txt"),
+///     &mut provenance,
+/// )
+/// .unwrap();
+///
+/// let explanations = provenance.explain(&trie, SortOrder::Address);
+/// assert_eq!(explanations[0].prefix.to_string(), "10.0.0.0/8");
+/// assert_eq!(explanations[0].inputs.len(), 2);
+/// assert_eq!(explanations[0].inputs[1].1.to_string(), "feed.txt:2");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Provenance {
+    inputs: Vec<(IpNet, Origin)>,
+    recorded: Recorded,
+    /// Records stored in `inputs`, only kept without line numbers to skip identical records
+    seen: HashSet<(IpNet, Origin)>,
+}
+
+impl Provenance {
+    /// Creates a new empty `Provenance` recording the complete origin of each prefix.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty `Provenance` recording only the given parts of the origins.
+    pub fn recording(recorded: Recorded) -> Self {
+        Self {
+            recorded,
+            ..Self::default()
+        }
+    }
+
+    /// Records the origin of a prefix read from the input.
+    pub fn record(&mut self, prefix: IpNet, mut origin: Origin) {
+        let recorded = self.recorded;
+        if !recorded.sources {
+            origin.source = None;
+        }
+        if !recorded.annotations {
+            origin.annotation = None;
+        }
+
+        if !recorded.line_numbers {
+            origin.line_number = 0;
+            if !self.seen.insert((prefix, origin.clone())) {
+                return;
+            }
+        }
+
+        self.inputs.push((prefix, origin));
+    }
+
+    /// Returns the number of recorded prefixes.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Returns `true` if no prefix was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Assigns each recorded prefix to the prefixes of the trie overlapping it. Recorded prefixes
+    /// not overlapping any prefix of the trie (e.g. excluded ones) are not part of the result.
+    /// The explanations are returned in the given order.
+    pub fn explain(&self, trie: &ReduceTrie, order: SortOrder) -> Vec<Explanation> {
+        let mut explanations: Vec<Explanation> = trie
+            .get_sorted_prefixes(order)
+            .into_iter()
+            .map(|prefix| Explanation {
+                prefix,
+                inputs: Vec::new(),
+            })
+            .collect();
+
+        // the prefixes of the trie do not overlap, so they can be looked up by network address
+        let index: BTreeMap<IpNet, usize> = explanations
+            .iter()
+            .enumerate()
+            .map(|(idx, e)| (e.prefix.trunc(), idx))
+            .collect();
+
+        for (prefix, origin) in &self.inputs {
+            for idx in overlapping(&index, prefix) {
+                explanations[idx].inputs.push((*prefix, origin.clone()));
+            }
+        }
+
+        explanations
+    }
+}
+
+/// Returns the indexes of the non overlapping prefixes covering or contained in `prefix`.
+fn overlapping(index: &BTreeMap<IpNet, usize>, prefix: &IpNet) -> Vec<usize> {
+    // a covering prefix would be the closest one starting at or before the network address
+    let covering = index
+        .range(..=host(prefix.network(), prefix))
+        .next_back()
+        .filter(|(p, _)| p.contains(prefix));
+    if let Some((_, idx)) = covering {
+        return vec![*idx];
+    }
+
+    let first = IpNet::new(prefix.network(), 0).expect("prefix length 0 is valid");
+    let last = host(prefix.broadcast(), prefix);
+    index
+        .range(first..=last)
+        .filter(|(p, _)| prefix.contains(*p))
+        .map(|(_, idx)| *idx)
+        .collect()
+}
+
+fn host(addr: IpAddr, prefix: &IpNet) -> IpNet {
+    IpNet::new(addr, prefix.max_prefix_len()).expect("maximum prefix length is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cidr::ParseOptions;
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    fn explain(input: &str, trie: impl FnOnce(&mut ReduceTrie)) -> Vec<(String, Vec<usize>)> {
+        let mut provenance = Provenance::new();
+        let (mut reduced, _) = ReduceTrie::from_reader_with_provenance(
+            input.as_bytes(),
+            ParseOptions::default(),
+            None,
+            &mut provenance,
+        )
+        .unwrap();
+        trie(&mut reduced);
+
+        provenance
+            .explain(&reduced, SortOrder::Address)
+            .into_iter()
+            .map(|e| {
+                let lines = e.inputs.iter().map(|(_, o)| o.line_number).collect();
+                (e.prefix.to_string(), lines)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_explain_covered_prefixes() {
+        let input = "10.1.0.0/16\n10.0.0.0/8\n\n10.0.0.1\n192.168.1.1\n2001:db8::/32\n";
+
+        assert_eq!(
+            vec![
+                ("10.0.0.0/8".to_string(), vec![1, 2, 4]),
+                ("192.168.1.1/32".to_string(), vec![5]),
+                ("2001:db8::/32".to_string(), vec![6]),
+            ],
+            explain(input, |_| {})
+        );
+    }
+
+    #[test]
+    fn test_explain_aggregated_prefixes() {
+        let input = "10.0.0.0/25\n10.0.0.128/25\n10.0.0.1\n";
+
+        assert_eq!(
+            vec![("10.0.0.0/24".to_string(), vec![1, 2, 3])],
+            explain(input, ReduceTrie::aggregate)
+        );
+    }
+
+    #[test]
+    fn test_explain_split_prefixes() {
+        let input = "10.0.0.0/24\n10.0.0.1\n";
+        let excluded = ReduceTrie::from_prefixes(vec![net("10.0.0.0/25")]);
+
+        assert_eq!(
+            vec![("10.0.0.128/25".to_string(), vec![1])],
+            explain(input, |trie| trie.exclude(&excluded))
+        );
+    }
+
+    #[test]
+    fn test_explain_keeps_annotations() {
+        let input = "10.0.0.0/8 # RFC1918\n10.1.0.0/16\n";
+        let mut provenance = Provenance::new();
+        let (trie, _) = ReduceTrie::from_reader_with_provenance(
+            input.as_bytes(),
+            ParseOptions::default(),
+            None,
+            &mut provenance,
+        )
+        .unwrap();
+
+        let explanations = provenance.explain(&trie, SortOrder::Address);
+        let annotations: Vec<_> = explanations[0]
+            .inputs
+            .iter()
+            .map(|(_, o)| o.annotation.as_deref())
+            .collect();
+        assert_eq!(vec![Some("RFC1918"), None], annotations);
+    }
+
+    #[test]
+    fn test_record_without_line_numbers() {
+        let input = "10.0.0.0/8 # RFC1918\n10.0.0.0/8 # RFC1918\n10.0.0.0/8\n10.1.0.0/16\n";
+        let recorded = Recorded {
+            sources: true,
+            line_numbers: false,
+            annotations: false,
+        };
+        let mut provenance = Provenance::recording(recorded);
+        for source in ["a.txt", "b.txt"] {
+            ReduceTrie::from_reader_with_provenance(
+                input.as_bytes(),
+                ParseOptions::default(),
+                Some(source),
+                &mut provenance,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(4, provenance.len());
+
+        let origin = |source: &str| Origin {
+            source: Some(source.into()),
+            line_number: 0,
+            annotation: None,
+        };
+        assert_eq!(
+            vec![
+                (net("10.0.0.0/8"), origin("a.txt")),
+                (net("10.1.0.0/16"), origin("a.txt")),
+                (net("10.0.0.0/8"), origin("b.txt")),
+                (net("10.1.0.0/16"), origin("b.txt")),
+            ],
+            provenance.inputs
+        );
+    }
+
+    #[test]
+    fn test_origin_display() {
+        let origin = Origin {
+            source: Some("feed.txt".into()),
+            line_number: 3,
+            annotation: Some("RFC1918".into()),
+        };
+        assert_eq!("feed.txt:3", origin.to_string());
+
+        let origin = Origin {
+            source: None,
+            line_number: 3,
+            annotation: None,
+        };
+        assert_eq!("line 3", origin.to_string());
+    }
+}
@@ -1,68 +1,466 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, BufRead};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use ipnet::IpNet;
 use rayon::prelude::*;
 
-/// A node in the prefix trie.
-#[derive(Default)]
+use crate::build_stats::{BuildStats, FamilyStats};
+use crate::cidr::{self, ParseOptions};
+use crate::ipv4_embedding::Ipv4Embedding;
+use crate::lossy_aggregation;
+use crate::provenance::{Origin, Provenance};
+use crate::report::ParseReport;
+use crate::sort_order::{self, SortOrder};
+
+/// A node in the prefix trie. The prefix is stored together with its sequence number, which is
+/// the position of its first occurrence in the input.
+#[derive(Default, Clone)]
 struct Node {
     children: [Option<Box<Node>>; 2],
-    prefix: Option<IpNet>,
+    prefix: Option<(IpNet, usize)>,
+}
+
+impl Node {
+    fn is_empty(&self) -> bool {
+        self.prefix.is_none() && self.children.iter().all(Option::is_none)
+    }
 }
 
-/// A table for a specific IP family (IPv4 or IPv6)
+/// A table for a specific IP family (IPv4 or IPv6). Host prefixes are kept apart from the trie
+/// (mapped to their sequence number), since they are by far the most common entries.
+#[derive(Default, Clone)]
 struct Table {
     root: Node,
-    hosts: Vec<IpNet>,
+    hosts: HashMap<IpNet, usize>,
+}
+
+impl Table {
+    /// Adds a single prefix while reading the input. Hosts not yet covered are kept until
+    /// `remove_covered_hosts` is called, since prefixes read later might still cover them.
+    fn add(&mut self, prefix: IpNet, seq: usize) {
+        if prefix.prefix_len() < prefix.max_prefix_len() {
+            ReduceTrie::insert_into_tree(&mut self.root, prefix, seq);
+            return;
+        }
+
+        // hosts already covered can be dropped right away to keep the host table small
+        if !ReduceTrie::is_covered(&self.root, prefix) {
+            add_host(&mut self.hosts, prefix, seq);
+        }
+    }
+
+    fn remove_covered_hosts(&mut self) {
+        let root = &self.root;
+
+        self.hosts = std::mem::take(&mut self.hosts)
+            .into_par_iter()
+            .filter(|&(p, _)| !ReduceTrie::is_in_tree(root, p))
+            .collect();
+    }
+
+    /// Returns the number of prefixes in the table.
+    fn len(&self) -> usize {
+        count_prefixes(&self.root) + self.hosts.len()
+    }
+}
+
+/// Adds a host keeping its first occurrence, returns `true` if it was added before.
+fn add_host(hosts: &mut HashMap<IpNet, usize>, host: IpNet, seq: usize) -> bool {
+    match hosts.entry(host) {
+        std::collections::hash_map::Entry::Occupied(_) => true,
+        std::collections::hash_map::Entry::Vacant(entry) => {
+            entry.insert(seq);
+            false
+        }
+    }
+}
+
+/// Returns the time spent in `f` together with its result.
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+
+    (result, start.elapsed())
 }
 
 /// A trie structure to reduce IP prefixes. The trie only stores the less specific prefixes for
 /// each IPv4 or IPv6 address.
+///
+/// # Examples
+///
+/// ```
+/// use net_reduce::ReduceTrie;
+///
+/// let input = "10.0.0.0/8\n10.1.0.0/16\n192.168.1.1\n";
+/// let (trie, report) = ReduceTrie::from_reader(input.as_bytes()).unwrap();
+///
+/// assert!(report.is_empty());
+/// assert_eq!(trie.get_all_prefixes().len(), 2);
+/// ```
+///
+/// Prefixes can also be added one by one or from any iterator of `IpNet`:
+///
+/// ```
+/// use std::net::IpAddr;
+///
+/// use ipnet::IpNet;
+/// use net_reduce::ReduceTrie;
+///
+/// let mut trie: ReduceTrie = ["10.1.0.0/16", "192.168.1.1/32"]
+///     .iter()
+///     .map(|p| p.parse::<IpNet>().unwrap())
+///     .collect();
+/// trie.insert("10.0.0.0/8".parse().unwrap());
+///
+/// assert!(trie.contains("10.2.3.4".parse::<IpAddr>().unwrap()));
+/// assert!(trie.covers("10.1.0.0/16".parse().unwrap()));
+/// assert!(!trie.covers("192.168.1.0/24".parse().unwrap()));
+/// assert_eq!(trie.iter().count(), 2);
+/// ```
+#[derive(Default, Clone)]
 pub struct ReduceTrie {
     ipv4: Table,
     ipv6: Table,
+    /// Sequence number assigned to the next prefix added
+    next_seq: usize,
+    /// All prefixes inserted (truncated to their network address) with their sequence numbers,
+    /// only kept by tries created with `incremental`
+    history: Option<BTreeMap<IpNet, usize>>,
+    /// Statistics of the prefixes added in bulk
+    stats: BuildStats,
 }
 
 impl ReduceTrie {
+    /// Creates a new empty `ReduceTrie`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty `ReduceTrie` supporting `remove` of prefixes covered by others.
+    ///
+    /// The trie remembers every prefix inserted (including the ones covered by less specific
+    /// prefixes), so removing a prefix re-exposes the more specific prefixes it covered. This
+    /// costs memory proportional to the number of prefixes inserted. The history is discarded by
+    /// `aggregate`, `aggregate_lossy`, `exclude`, `union` and `intersect`, since the prefixes
+    /// they produce no longer correspond to the inserted ones.
+    pub fn incremental() -> Self {
+        Self {
+            history: Some(BTreeMap::new()),
+            ..Self::default()
+        }
+    }
+
     /// Creates a new `ReduceTrie` with the given prefixes.
     pub fn from_prefixes(prefixes: Vec<IpNet>) -> Self {
-        let (ipv4_prefixes, ipv6_prefixes): (Vec<_>, Vec<_>) = prefixes
-            .into_iter()
-            .partition(|p| matches!(p, IpNet::V4(_)));
+        let mut trie = Self::new();
+        trie.extend(prefixes);
 
-        let (ipv4, ipv6) = rayon::join(
-            || Self::build_for_family(ipv4_prefixes),
-            || Self::build_for_family(ipv6_prefixes),
+        trie
+    }
+
+    /// Creates a new `ReduceTrie` with the given prefixes, converting IPv6 prefixes embedding IPv4
+    /// addresses (e.g. ::ffff:192.0.2.1) into their IPv4 family first, so they are reduced
+    /// together with the IPv4 prefixes.
+    pub fn from_prefixes_with_embedding(prefixes: Vec<IpNet>, embedding: Ipv4Embedding) -> Self {
+        Self::from_prefixes(
+            prefixes
+                .into_iter()
+                .map(|p| embedding.canonicalize(p))
+                .collect(),
+        )
+    }
+
+    fn from_entries(entries: Vec<(IpNet, usize)>) -> Self {
+        let mut trie = Self::new();
+        trie.next_seq = entries.iter().map(|(_, seq)| seq + 1).max().unwrap_or(0);
+        trie.extend_entries(entries);
+
+        trie
+    }
+
+    /// Creates a new `ReduceTrie` by parsing the reader line by line. Each prefix is inserted
+    /// into the table of its family right away, so the input is never held in memory as a
+    /// whole (only the distinct prefixes read are remembered to count duplicates). Blank lines and comments (starting with `#`, `;` or `//`) are skipped, lines which
+    /// can not be parsed are collected in the returned `ParseReport`.
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<(Self, ParseReport)> {
+        Self::from_reader_with_options(reader, ParseOptions::default())
+    }
+
+    /// Creates a new `ReduceTrie` like `from_reader`, parsing the lines with the given options.
+    pub fn from_reader_with_options<R: BufRead>(
+        reader: R,
+        options: ParseOptions,
+    ) -> io::Result<(Self, ParseReport)> {
+        Self::read(reader, options, |_, _, _| {})
+    }
+
+    /// Creates a new `ReduceTrie` like `from_reader`, additionally recording the origin of every
+    /// prefix read (including the ones removed by reduction) in `provenance`. `source` names the
+    /// input, e.g. the file name.
+    pub fn from_reader_with_provenance<R: BufRead>(
+        reader: R,
+        options: ParseOptions,
+        source: Option<&str>,
+        provenance: &mut Provenance,
+    ) -> io::Result<(Self, ParseReport)> {
+        let source: Option<Arc<str>> = source.map(Arc::from);
+
+        Self::read(reader, options, |prefix, line_number, annotation| {
+            let origin = Origin {
+                source: source.clone(),
+                line_number,
+                annotation: annotation.map(Arc::from),
+            };
+            provenance.record(prefix, origin);
+        })
+    }
+
+    fn read<R: BufRead>(
+        mut reader: R,
+        options: ParseOptions,
+        mut on_prefix: impl FnMut(IpNet, usize, Option<&str>),
+    ) -> io::Result<(Self, ParseReport)> {
+        let mut trie = Self::new();
+        let mut report = ParseReport::default();
+        let mut stats = BuildStats::default();
+        let mut seen = HashSet::new();
+
+        let mut line = String::new();
+        let mut line_number = 0;
+
+        let start = Instant::now();
+        while reader.read_line(&mut line)? > 0 {
+            line_number += 1;
+
+            let text = line.trim_end_matches(['\n', '\r']);
+            match cidr::parse_annotated_line(text, options) {
+                Some(Ok((prefixes, annotation))) => {
+                    for prefix in prefixes {
+                        on_prefix(prefix, line_number, annotation);
+                        trie.table_mut(&prefix).add(prefix, line_number);
+                        report.prefixes += 1;
+
+                        let family = stats.family_mut(&prefix);
+                        family.inputs += 1;
+                        family.duplicates += usize::from(!seen.insert(prefix.trunc()));
+                    }
+                }
+                Some(Err(e)) => report.reject(line_number, text, e),
+                None => {}
+            }
+
+            line.clear();
+        }
+        let build_time = start.elapsed();
+
+        let ((_, ipv4_time), (_, ipv6_time)) = rayon::join(
+            || timed(|| trie.ipv4.remove_covered_hosts()),
+            || timed(|| trie.ipv6.remove_covered_hosts()),
         );
+        trie.next_seq = line_number + 1;
+
+        for (family, table, time) in [
+            (&mut stats.ipv4, &trie.ipv4, ipv4_time),
+            (&mut stats.ipv6, &trie.ipv6, ipv6_time),
+        ] {
+            family.covered = family.inputs - family.duplicates - table.len();
+            family.build_time = build_time;
+            family.coverage_time = time;
+        }
+        trie.stats = stats;
 
-        ReduceTrie { ipv4, ipv6 }
+        Ok((trie, report))
     }
 
-    fn build_for_family(prefixes: Vec<IpNet>) -> Table {
-        let mut root = Node::default();
+    fn table(&self, prefix: &IpNet) -> &Table {
+        match prefix {
+            IpNet::V4(_) => &self.ipv4,
+            IpNet::V6(_) => &self.ipv6,
+        }
+    }
 
-        let sorted_prefixes = sort_prefixes(prefixes);
+    fn table_mut(&mut self, prefix: &IpNet) -> &mut Table {
+        match prefix {
+            IpNet::V4(_) => &mut self.ipv4,
+            IpNet::V6(_) => &mut self.ipv6,
+        }
+    }
 
-        let (net_prefixes, host_prefixes): (Vec<_>, Vec<_>) = sorted_prefixes
-            .into_iter()
-            .partition(|p| p.prefix_len() < p.max_prefix_len());
+    /// Inserts a single prefix. The prefix is ignored if it is already covered, prefixes covered
+    /// by it are removed.
+    pub fn insert(&mut self, prefix: IpNet) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
 
-        for prefix in net_prefixes {
-            Self::insert_into_tree(&mut root, prefix);
+        if let Some(history) = &mut self.history {
+            history.entry(prefix.trunc()).or_insert(seq);
         }
 
-        let mut seen = HashSet::new();
-        let host_prefixes: Vec<_> = host_prefixes.into_iter().filter(|p| seen.insert(*p)).collect();
+        self.insert_entry(prefix, seq);
+    }
 
-        let hosts = host_prefixes
-            .into_par_iter()
-            .filter(|&p| !Self::is_covered(&root, p))
+    fn insert_entry(&mut self, prefix: IpNet, seq: usize) {
+        let table = self.table_mut(&prefix);
+
+        if prefix.prefix_len() == prefix.max_prefix_len() {
+            if !Self::is_in_tree(&table.root, prefix) {
+                table.hosts.entry(prefix).or_insert(seq);
+            }
+            return;
+        }
+
+        Self::insert_into_tree(&mut table.root, prefix, seq);
+        remove_hosts_within(&mut table.hosts, prefix);
+    }
+
+    /// Removes a single prefix, returns `false` if it was not found.
+    ///
+    /// For tries created with `incremental` any previously inserted prefix can be removed and
+    /// the more specific prefixes covered by it become visible again. Otherwise only prefixes
+    /// left after reduction can be removed, since covered prefixes are not kept.
+    pub fn remove(&mut self, prefix: IpNet) -> bool {
+        if let Some(history) = &mut self.history
+            && history.remove(&prefix.trunc()).is_none()
+        {
+            return false;
+        }
+
+        let table = self.table_mut(&prefix);
+        let removed = if prefix.prefix_len() == prefix.max_prefix_len() {
+            table.hosts.remove(&prefix).is_some()
+        } else {
+            remove_from_tree(&mut table.root, &prefix, 0)
+        };
+
+        if !removed {
+            // the prefix was covered by another one, so the reduced set does not change
+            return self.history.is_some();
+        }
+
+        for (p, seq) in self.uncovered_history(prefix) {
+            self.insert_entry(p, seq);
+        }
+
+        true
+    }
+
+    /// Returns the prefixes of the history contained in `prefix`, less specific ones first.
+    fn uncovered_history(&self, prefix: IpNet) -> Vec<(IpNet, usize)> {
+        let Some(history) = &self.history else {
+            return Vec::new();
+        };
+
+        let first = IpNet::new(prefix.network(), 0).expect("prefix length 0 is valid");
+        let last = IpNet::new(prefix.broadcast(), prefix.max_prefix_len())
+            .expect("maximum prefix length is valid");
+
+        let mut result: Vec<_> = history
+            .range(first..=last)
+            .filter(|(p, _)| prefix.contains(*p))
+            .map(|(p, seq)| (*p, *seq))
             .collect();
+        result.sort_by_key(|(p, _)| p.prefix_len());
+
+        result
+    }
 
-        Table { root, hosts }
+    /// Returns `true` if the address is covered by any of the prefixes.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        self.covers(IpNet::from(addr))
     }
 
-    fn insert_into_tree(root: &mut Node, prefix: IpNet) {
+    /// Returns `true` if the prefix is completely covered by one of the prefixes.
+    pub fn covers(&self, prefix: IpNet) -> bool {
+        let table = self.table(&prefix);
+
+        Self::is_in_tree(&table.root, prefix) || table.hosts.contains_key(&prefix)
+    }
+
+    /// Returns `true` if any address of the prefix is covered by one of the prefixes.
+    fn overlaps(&self, prefix: &IpNet) -> bool {
+        let table = self.table(prefix);
+
+        overlaps_tree(&table.root, prefix) || table.hosts.keys().any(|host| prefix.contains(host))
+    }
+
+    /// Returns a lazy iterator over all prefixes left after reduction. The prefixes are not
+    /// sorted but returned in the order of `SortOrder::None`, use `get_all_prefixes` for sorted
+    /// prefixes.
+    pub fn iter(&self) -> impl Iterator<Item = IpNet> + '_ {
+        table_prefixes(&self.ipv4).chain(table_prefixes(&self.ipv6))
+    }
+
+    /// Returns the statistics of the prefixes added in bulk, see `BuildStats`. `union` and
+    /// `intersect` add the statistics of the other trie.
+    pub fn stats(&self) -> BuildStats {
+        self.stats
+    }
+
+    /// Adds the entries to the tables of their family, returns the statistics of the prefixes
+    /// added.
+    fn extend_entries(&mut self, entries: Vec<(IpNet, usize)>) -> BuildStats {
+        let (ipv4_prefixes, ipv6_prefixes): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .partition(|(p, _)| matches!(p, IpNet::V4(_)));
+
+        let ReduceTrie { ipv4, ipv6, .. } = self;
+
+        let (ipv4, ipv6) = rayon::join(
+            || Self::build_for_family(ipv4, ipv4_prefixes),
+            || Self::build_for_family(ipv6, ipv6_prefixes),
+        );
+
+        BuildStats { ipv4, ipv6 }
+    }
+
+    fn build_for_family(table: &mut Table, prefixes: Vec<(IpNet, usize)>) -> FamilyStats {
+        let mut stats = FamilyStats {
+            inputs: prefixes.len(),
+            ..Default::default()
+        };
+        let existing = table.len();
+
+        let (_, build_time) = timed(|| {
+            // repeated prefixes are removed first, so they count as duplicates even if covered
+            let mut distinct: HashMap<IpNet, (IpNet, usize)> = HashMap::new();
+            for (prefix, seq) in prefixes {
+                let first = distinct.entry(prefix.trunc()).or_insert((prefix, seq));
+                first.1 = first.1.min(seq);
+            }
+            stats.duplicates = stats.inputs - distinct.len();
+
+            let sorted_prefixes = sort_prefixes(distinct.into_values().collect());
+
+            let (net_prefixes, host_prefixes): (Vec<_>, Vec<_>) = sorted_prefixes
+                .into_iter()
+                .partition(|(p, _)| p.prefix_len() < p.max_prefix_len());
+
+            for (prefix, seq) in net_prefixes {
+                let duplicate = Self::insert_into_tree(&mut table.root, prefix, seq);
+                stats.duplicates += usize::from(duplicate);
+            }
+
+            for (prefix, seq) in host_prefixes {
+                let duplicate = add_host(&mut table.hosts, prefix, seq);
+                stats.duplicates += usize::from(duplicate);
+            }
+        });
+
+        let (_, coverage_time) = timed(|| table.remove_covered_hosts());
+
+        stats.covered = existing + stats.inputs - stats.duplicates - table.len();
+        stats.build_time = build_time;
+        stats.coverage_time = coverage_time;
+
+        stats
+    }
+
+    /// Inserts the prefix into the trie unless it is covered already, returns `true` if the same
+    /// prefix was inserted before and is still in the trie.
+    fn insert_into_tree(root: &mut Node, prefix: IpNet, seq: usize) -> bool {
         let prefix_len = prefix.prefix_len() as usize;
         let mut node = root;
 
@@ -71,15 +469,23 @@ impl ReduceTrie {
 
             if node.prefix.is_some() {
                 // the prefix is already covered
-                return;
+                return false;
             }
 
             node = node.children[bit].get_or_insert_with(Box::default);
         }
 
-        node.prefix = Some(prefix);
+        if let Some((_, existing_seq)) = &mut node.prefix {
+            // the same prefix was inserted before, keep its first occurrence
+            *existing_seq = (*existing_seq).min(seq);
+            return true;
+        }
+
+        node.prefix = Some((prefix, seq));
         node.children[0] = None;
         node.children[1] = None;
+
+        false
     }
 
     fn is_covered(root: &Node, prefix: IpNet) -> bool {
@@ -104,19 +510,246 @@ impl ReduceTrie {
         false
     }
 
-    /// Returns all prefixes left after reduction.
+    /// Returns `true` if the prefix is stored in the trie or covered by a prefix stored in it.
+    fn is_in_tree(root: &Node, prefix: IpNet) -> bool {
+        let mut node = root;
+
+        for pos in 0..prefix.prefix_len() as usize {
+            if node.prefix.is_some() {
+                return true;
+            }
+
+            match &node.children[get_bit(&prefix, pos) as usize] {
+                Some(child) => node = child,
+                None => return false,
+            }
+        }
+
+        node.prefix.is_some()
+    }
+
+    /// Removes the address space covered by `other` from this trie. Prefixes only partially
+    /// covered are split into the minimal set of prefixes covering the remaining addresses
+    /// (e.g. 10.0.0.0/8 without 10.1.0.0/16 results in 10.0.0.0/16, 10.2.0.0/15, ...,
+    /// 10.128.0.0/9).
+    pub fn exclude(&mut self, other: &ReduceTrie) {
+        self.history = None;
+
+        let ReduceTrie { ipv4, ipv6, .. } = self;
+
+        rayon::join(
+            || Self::exclude_family(ipv4, &other.ipv4),
+            || Self::exclude_family(ipv6, &other.ipv6),
+        );
+    }
+
+    fn exclude_family(table: &mut Table, excluded: &Table) {
+        let mut excluded_prefixes = Vec::new();
+        collect_prefixes(&excluded.root, &mut excluded_prefixes);
+        excluded_prefixes.extend(excluded.hosts.iter().map(|(p, seq)| (*p, *seq)));
+
+        for (prefix, _) in &excluded_prefixes {
+            subtract_from_tree(&mut table.root, prefix, 0);
+        }
+
+        table.hosts = std::mem::take(&mut table.hosts)
+            .into_par_iter()
+            .filter(|(p, _)| {
+                !excluded.hosts.contains_key(p) && !Self::is_in_tree(&excluded.root, *p)
+            })
+            .collect();
+    }
+
+    /// Adds the address space covered by `other` to this trie.
+    pub fn union(&mut self, other: &ReduceTrie) {
+        self.history = None;
+
+        let offset = self.next_seq;
+        self.next_seq += other.next_seq;
+
+        let entries = other
+            .entries()
+            .into_iter()
+            .map(|(p, seq)| (p, seq + offset))
+            .collect();
+        let mut merged = self.extend_entries(entries);
+        // The prefixes of `other` are counted as inputs by its own statistics already.
+        merged.ipv4.inputs = 0;
+        merged.ipv6.inputs = 0;
+        self.stats += other.stats;
+        self.stats += merged;
+    }
+
+    /// Restricts this trie to the address space covered by both this trie and `other`.
+    pub fn intersect(&mut self, other: &ReduceTrie) {
+        self.history = None;
+        self.stats += other.stats;
+
+        let ReduceTrie { ipv4, ipv6, .. } = self;
+
+        let (ipv4, ipv6) = rayon::join(
+            || Self::intersect_family(ipv4, &other.ipv4),
+            || Self::intersect_family(ipv6, &other.ipv6),
+        );
+        self.stats += BuildStats { ipv4, ipv6 };
+    }
+
+    /// Restricts the table to the intersection with `other`. Returns the statistics of the
+    /// operation: prefixes of both tables are counted as duplicates, the ones outside the
+    /// intersection as covered.
+    fn intersect_family(table: &mut Table, other: &Table) -> FamilyStats {
+        let own: HashSet<IpNet> = table_prefixes(table).map(|p| p.trunc()).collect();
+        let common = table_prefixes(other)
+            .filter(|p| own.contains(&p.trunc()))
+            .count();
+        let distinct = own.len() + other.len() - common;
+
+        let mut prefixes = Vec::new();
+        intersect_trees(&table.root, &other.root, &mut prefixes);
+
+        let table_hosts = table
+            .hosts
+            .iter()
+            .filter(|(p, _)| other.hosts.contains_key(p) || Self::is_in_tree(&other.root, **p));
+        let other_hosts = other
+            .hosts
+            .iter()
+            .filter(|(p, _)| Self::is_in_tree(&table.root, **p));
+        prefixes.extend(table_hosts.chain(other_hosts).map(|(p, seq)| (*p, *seq)));
+
+        *table = Table::default();
+        let built = Self::build_for_family(table, prefixes);
+
+        FamilyStats {
+            inputs: 0,
+            duplicates: common,
+            covered: distinct - table.len(),
+            ..built
+        }
+    }
+
+    /// Compares this trie with `new` by the address space they cover. Returns the aggregated
+    /// prefixes only covered by `new` (added) and the ones only covered by this trie (removed).
+    pub fn diff(&self, new: &ReduceTrie) -> (ReduceTrie, ReduceTrie) {
+        let mut added = new.clone();
+        added.exclude(self);
+        added.aggregate();
+
+        let mut removed = self.clone();
+        removed.exclude(new);
+        removed.aggregate();
+
+        (added, removed)
+    }
+
+    /// Aggregates the reduced prefixes by merging adjacent siblings into their supernet (e.g.
+    /// 10.0.0.0/25 and 10.0.0.128/25 become 10.0.0.0/24). Host prefixes take part in the merging
+    /// as well. The covered address space stays exactly the same.
+    pub fn aggregate(&mut self) {
+        self.history = None;
+
+        let ReduceTrie { ipv4, ipv6, .. } = self;
+
+        rayon::join(
+            || Self::aggregate_family(ipv4),
+            || Self::aggregate_family(ipv6),
+        );
+    }
+
+    /// Aggregates the prefixes and then keeps merging the neighbouring prefixes adding the least
+    /// addresses until at most `max_entries` prefixes are left. Returns the number of addresses
+    /// covered in addition to the original prefixes.
+    pub fn aggregate_lossy(&mut self, max_entries: usize) -> u128 {
+        self.aggregate_lossy_excluding(max_entries, &ReduceTrie::new())
+    }
+
+    /// Same as `aggregate_lossy`, but merges resulting in a prefix overlapping the address space
+    /// of `excluded` are skipped. Fewer merges may be possible then, so more than `max_entries`
+    /// prefixes can be left.
+    pub fn aggregate_lossy_excluding(&mut self, max_entries: usize, excluded: &ReduceTrie) -> u128 {
+        self.aggregate();
+
+        let mut excluded = excluded.clone();
+        excluded.aggregate();
+
+        let (next_seq, stats) = (self.next_seq, self.stats);
+        let (entries, added) =
+            lossy_aggregation::merge_cheapest(self.entries(), max_entries, |supernet| {
+                !excluded.overlaps(supernet)
+            });
+        *self = Self::from_entries(entries);
+        self.next_seq = next_seq;
+        self.stats = stats;
+
+        added
+    }
+
+    fn aggregate_family(table: &mut Table) {
+        for (host, seq) in table.hosts.drain() {
+            Self::insert_into_tree(&mut table.root, host, seq);
+        }
+
+        merge_siblings(&mut table.root);
+    }
+
+    /// Returns all prefixes left after reduction, sorted by IP family, address and prefix
+    /// length.
     pub fn get_all_prefixes(&self) -> Vec<IpNet> {
+        self.get_sorted_prefixes(SortOrder::default())
+    }
+
+    /// Returns all prefixes left after reduction in the given order.
+    pub fn get_sorted_prefixes(&self, order: SortOrder) -> Vec<IpNet> {
+        let mut entries = self.entries();
+        sort_order::sort(&mut entries, order);
+
+        entries.into_iter().map(|(p, _)| p).collect()
+    }
+
+    /// Returns all prefixes with their sequence numbers. Prefixes of the trie are sorted by
+    /// address within each IP family, hosts follow in no particular order.
+    fn entries(&self) -> Vec<(IpNet, usize)> {
         let mut result = Vec::new();
 
         collect_prefixes(&self.ipv4.root, &mut result);
+        result.extend(self.ipv4.hosts.iter().map(|(p, seq)| (*p, *seq)));
         collect_prefixes(&self.ipv6.root, &mut result);
-        result.extend(self.ipv4.hosts.iter());
-        result.extend(self.ipv6.hosts.iter());
+        result.extend(self.ipv6.hosts.iter().map(|(p, seq)| (*p, *seq)));
 
         result
     }
 }
 
+impl FromIterator<IpNet> for ReduceTrie {
+    fn from_iter<I: IntoIterator<Item = IpNet>>(iter: I) -> Self {
+        let mut trie = Self::new();
+        trie.extend(iter);
+
+        trie
+    }
+}
+
+impl Extend<IpNet> for ReduceTrie {
+    fn extend<I: IntoIterator<Item = IpNet>>(&mut self, iter: I) {
+        let first_seq = self.next_seq;
+        let entries: Vec<_> = iter
+            .into_iter()
+            .enumerate()
+            .map(|(idx, p)| (p, first_seq + idx))
+            .collect();
+
+        if let Some(history) = &mut self.history {
+            for (p, seq) in &entries {
+                history.entry(p.trunc()).or_insert(*seq);
+            }
+        }
+
+        self.next_seq += entries.len();
+        let stats = self.extend_entries(entries);
+        self.stats += stats;
+    }
+}
+
 fn get_bit(prefix: &IpNet, pos: usize) -> u8 {
     let byte_idx = pos >> 3; // divide by 8
     let bit_idx = 7 - (pos & 7); // modulo 8
@@ -133,15 +766,18 @@ fn get_bit(prefix: &IpNet, pos: usize) -> u8 {
     }
 }
 
-fn sort_prefixes(prefixes: Vec<IpNet>) -> Vec<IpNet> {
+fn sort_prefixes(prefixes: Vec<(IpNet, usize)>) -> Vec<(IpNet, usize)> {
     // reasoning: we use the grouping approach here, since it is very expensive to sort for prefix length on
     // large sets of prefixes. in this case we can sort by iterating over the preixes (O(n)) and
     // then sorting the keys (O(m log m)), where m is the number of unique prefix lengths.
 
-    let mut grouped_prefixes: HashMap<u8, Vec<IpNet>> = HashMap::new();
+    let mut grouped_prefixes: HashMap<u8, Vec<(IpNet, usize)>> = HashMap::new();
 
     for p in prefixes {
-        grouped_prefixes.entry(p.prefix_len()).or_default().push(p);
+        grouped_prefixes
+            .entry(p.0.prefix_len())
+            .or_default()
+            .push(p);
     }
 
     let mut keys: Vec<u8> = grouped_prefixes.keys().copied().collect();
@@ -155,7 +791,111 @@ fn sort_prefixes(prefixes: Vec<IpNet>) -> Vec<IpNet> {
     result
 }
 
-fn collect_prefixes(node: &Node, result: &mut Vec<IpNet>) {
+/// Removes all hosts contained in `net`. For small networks each address is looked up, otherwise
+/// all hosts are checked.
+fn remove_hosts_within(hosts: &mut HashMap<IpNet, usize>, net: IpNet) {
+    let host_bits = u32::from(net.max_prefix_len() - net.prefix_len());
+    let address_count = 1usize.checked_shl(host_bits).unwrap_or(usize::MAX);
+
+    if address_count > hosts.len() {
+        hosts.retain(|host, _| !net.contains(host));
+        return;
+    }
+
+    let addresses = net
+        .trunc()
+        .subnets(net.max_prefix_len())
+        .expect("maximum prefix length is valid");
+    for host in addresses {
+        hosts.remove(&host);
+    }
+}
+
+/// Removes the node storing exactly `prefix` and prunes the nodes left empty. Returns `false` if
+/// the prefix is not stored in the trie.
+fn remove_from_tree(node: &mut Node, prefix: &IpNet, depth: usize) -> bool {
+    if depth == prefix.prefix_len() as usize {
+        return node.prefix.take().is_some();
+    }
+
+    if node.prefix.is_some() {
+        // covered by a less specific prefix
+        return false;
+    }
+
+    let bit = get_bit(prefix, depth) as usize;
+    let Some(child) = &mut node.children[bit] else {
+        return false;
+    };
+
+    let removed = remove_from_tree(child, prefix, depth + 1);
+    if child.is_empty() {
+        node.children[bit] = None;
+    }
+
+    removed
+}
+
+fn count_prefixes(node: &Node) -> usize {
+    if node.prefix.is_some() {
+        return 1;
+    }
+
+    node.children
+        .iter()
+        .flatten()
+        .map(|child| count_prefixes(child))
+        .sum()
+}
+
+/// Returns the prefixes of the trie of the table by address (traversed depth first), followed by
+/// the hosts.
+fn table_prefixes(table: &Table) -> impl Iterator<Item = IpNet> + '_ {
+    let mut stack = vec![&table.root];
+    let tree = std::iter::from_fn(move || {
+        while let Some(node) = stack.pop() {
+            if let Some((prefix, _)) = node.prefix {
+                return Some(prefix);
+            }
+
+            // the lower half is visited first
+            stack.extend(node.children.iter().rev().flatten().map(|child| &**child));
+        }
+
+        None
+    });
+
+    tree.chain(table.hosts.keys().copied())
+}
+
+/// Returns `true` if a prefix of the trie covers `prefix` or is covered by it.
+fn overlaps_tree(root: &Node, prefix: &IpNet) -> bool {
+    let mut node = root;
+
+    for pos in 0..prefix.prefix_len() as usize {
+        if node.prefix.is_some() {
+            return true;
+        }
+
+        match &node.children[get_bit(prefix, pos) as usize] {
+            Some(child) => node = child,
+            None => return false,
+        }
+    }
+
+    has_prefix(node)
+}
+
+fn has_prefix(node: &Node) -> bool {
+    node.prefix.is_some()
+        || node
+            .children
+            .iter()
+            .flatten()
+            .any(|child| has_prefix(child))
+}
+
+fn collect_prefixes(node: &Node, result: &mut Vec<(IpNet, usize)>) {
     if let Some(prefix) = &node.prefix {
         result.push(*prefix);
         // don't traverse children of nodes with prefixes
@@ -169,3 +909,392 @@ fn collect_prefixes(node: &Node, result: &mut Vec<IpNet>) {
         collect_prefixes(child, result);
     }
 }
+
+/// Removes the address space of `prefix` from the subtree of `node` located at `depth`. Covering
+/// prefixes on the way down are split into their halves, so only the addresses of `prefix` are
+/// removed.
+fn subtract_from_tree(node: &mut Node, prefix: &IpNet, depth: usize) {
+    if depth == prefix.prefix_len() as usize {
+        node.prefix = None;
+        node.children[0] = None;
+        node.children[1] = None;
+        return;
+    }
+
+    if let Some((covering, seq)) = node.prefix.take() {
+        let halves = covering
+            .trunc()
+            .subnets(depth as u8 + 1)
+            .expect("prefix is shorter than the maximum length");
+
+        for (child, half) in node.children.iter_mut().zip(halves) {
+            *child = Some(Box::new(Node {
+                children: [None, None],
+                prefix: Some((half, seq)),
+            }));
+        }
+    }
+
+    let bit = get_bit(prefix, depth) as usize;
+    if let Some(child) = &mut node.children[bit] {
+        subtract_from_tree(child, prefix, depth + 1);
+
+        if child.is_empty() {
+            node.children[bit] = None;
+        }
+    }
+}
+
+/// Collects the prefixes covering the address space of both subtrees. Where one side stores a
+/// prefix, the other side is completely covered and contributes all of its prefixes.
+fn intersect_trees(a: &Node, b: &Node, result: &mut Vec<(IpNet, usize)>) {
+    if a.prefix.is_some() {
+        collect_prefixes(b, result);
+        return;
+    }
+
+    if b.prefix.is_some() {
+        collect_prefixes(a, result);
+        return;
+    }
+
+    for (a_child, b_child) in a.children.iter().zip(&b.children) {
+        if let (Some(a_child), Some(b_child)) = (a_child, b_child) {
+            intersect_trees(a_child, b_child, result);
+        }
+    }
+}
+
+fn merge_siblings(node: &mut Node) {
+    if node.prefix.is_some() {
+        return;
+    }
+
+    for child in node.children.iter_mut().flatten() {
+        merge_siblings(child);
+    }
+
+    // both halves are fully covered, so the node itself is covered
+    if let [Some(left), Some(right)] = &node.children
+        && let (Some((left_prefix, left_seq)), Some((_, right_seq))) = (left.prefix, right.prefix)
+    {
+        node.prefix = left_prefix
+            .supernet()
+            .map(|supernet| (supernet, left_seq.min(right_seq)));
+        node.children[0] = None;
+        node.children[1] = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_insert_removes_covered_prefixes() {
+        let mut trie = ReduceTrie::new();
+        trie.insert(net("10.0.0.1/32"));
+        trie.insert(net("10.0.1.0/24"));
+        trie.insert(net("10.1.0.1/32"));
+        trie.insert(net("10.0.0.0/16"));
+        trie.insert(net("10.0.2.0/24"));
+
+        assert_eq!(
+            vec![net("10.0.0.0/16"), net("10.1.0.1/32")],
+            trie.get_all_prefixes()
+        );
+    }
+
+    #[test]
+    fn test_insert_removes_covered_hosts_of_large_networks() {
+        let mut trie: ReduceTrie = [net("2001:db8::1/128"), net("2001:db9::1/128")]
+            .into_iter()
+            .collect();
+        trie.insert(net("2001:db8::/32"));
+
+        assert_eq!(
+            vec![net("2001:db8::/32"), net("2001:db9::1/128")],
+            trie.get_all_prefixes()
+        );
+    }
+
+    #[test]
+    fn test_insert_after_aggregate() {
+        let mut trie = ReduceTrie::from_prefixes(vec![net("10.0.0.1/32"), net("10.0.0.3/32")]);
+        trie.aggregate();
+        trie.insert(net("10.0.0.1/32"));
+
+        assert_eq!(
+            vec![net("10.0.0.1/32"), net("10.0.0.3/32")],
+            trie.get_all_prefixes()
+        );
+    }
+
+    #[test]
+    fn test_contains() {
+        let trie = ReduceTrie::from_prefixes(vec![net("10.0.0.0/8"), net("2001:db8::1/128")]);
+
+        assert!(trie.contains("10.1.2.3".parse().unwrap()));
+        assert!(trie.contains("2001:db8::1".parse().unwrap()));
+        assert!(!trie.contains("11.0.0.0".parse().unwrap()));
+        assert!(!trie.contains("2001:db8::2".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_covers() {
+        let trie = ReduceTrie::from_prefixes(vec![net("10.0.0.0/8"), net("192.168.1.1/32")]);
+
+        assert!(trie.covers(net("10.0.0.0/8")));
+        assert!(trie.covers(net("10.1.0.0/16")));
+        assert!(trie.covers(net("192.168.1.1/32")));
+        assert!(!trie.covers(net("10.0.0.0/7")));
+        assert!(!trie.covers(net("192.168.1.0/31")));
+    }
+
+    #[test]
+    fn test_from_prefixes_with_embedding() {
+        let prefixes = vec![
+            net("192.0.2.0/24"),
+            net("::ffff:192.0.2.1/128"),
+            net("64:ff9b::198.51.100.1/128"),
+        ];
+
+        let trie = ReduceTrie::from_prefixes_with_embedding(prefixes.clone(), Ipv4Embedding::Nat64);
+        assert_eq!(
+            vec![net("192.0.2.0/24"), net("198.51.100.1/32")],
+            trie.get_all_prefixes()
+        );
+
+        let trie = ReduceTrie::from_prefixes_with_embedding(prefixes, Ipv4Embedding::None);
+        assert_eq!(3, trie.get_all_prefixes().len());
+    }
+
+    #[test]
+    fn test_iter() {
+        let trie = ReduceTrie::from_prefixes(vec![
+            net("2001:db8::/32"),
+            net("10.1.0.0/16"),
+            net("192.0.2.1/32"),
+            net("10.0.0.0/16"),
+            net("10.0.1.0/24"),
+        ]);
+
+        assert_eq!(
+            vec![
+                net("10.0.0.0/16"),
+                net("10.1.0.0/16"),
+                net("192.0.2.1/32"),
+                net("2001:db8::/32"),
+            ],
+            trie.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut trie = ReduceTrie::from_prefixes(vec![net("10.0.0.1/32"), net("10.1.0.0/16")]);
+        trie.extend(vec![net("10.0.0.0/24"), net("10.2.0.1/32")]);
+
+        assert_eq!(
+            vec![net("10.0.0.0/24"), net("10.1.0.0/16"), net("10.2.0.1/32")],
+            trie.get_all_prefixes()
+        );
+        assert_eq!(
+            vec![net("10.1.0.0/16"), net("10.0.0.0/24"), net("10.2.0.1/32")],
+            trie.get_sorted_prefixes(SortOrder::InputOrder)
+        );
+    }
+
+    #[test]
+    fn test_remove_reduced_prefix() {
+        let mut trie = ReduceTrie::from_prefixes(vec![net("10.0.0.0/8"), net("192.168.1.1/32")]);
+
+        assert!(trie.remove(net("10.0.0.0/8")));
+        assert!(trie.remove(net("192.168.1.1/32")));
+        assert!(!trie.remove(net("10.1.0.0/16")));
+        assert!(trie.get_all_prefixes().is_empty());
+    }
+
+    #[test]
+    fn test_remove_reexposes_covered_prefixes() {
+        let mut trie = ReduceTrie::incremental();
+        trie.insert(net("10.0.0.0/8"));
+        trie.insert(net("10.1.0.0/16"));
+        trie.insert(net("10.1.1.0/24"));
+        trie.insert(net("10.2.0.1/32"));
+        trie.insert(net("11.0.0.1/32"));
+
+        assert!(trie.remove(net("10.0.0.0/8")));
+        assert_eq!(
+            vec![net("10.1.0.0/16"), net("10.2.0.1/32"), net("11.0.0.1/32")],
+            trie.get_all_prefixes()
+        );
+
+        assert!(trie.remove(net("10.1.0.0/16")));
+        assert_eq!(
+            vec![net("10.1.1.0/24"), net("10.2.0.1/32"), net("11.0.0.1/32")],
+            trie.get_all_prefixes()
+        );
+    }
+
+    #[test]
+    fn test_remove_covered_prefix() {
+        let mut trie = ReduceTrie::incremental();
+        trie.extend(vec![
+            net("10.0.0.0/8"),
+            net("10.1.0.0/16"),
+            net("10.0.0.1/32"),
+        ]);
+
+        assert!(trie.remove(net("10.1.0.0/16")));
+        assert!(trie.remove(net("10.0.0.1/32")));
+        assert!(!trie.remove(net("10.2.0.0/16")));
+        assert_eq!(vec![net("10.0.0.0/8")], trie.get_all_prefixes());
+
+        assert!(trie.remove(net("10.0.0.0/8")));
+        assert!(trie.get_all_prefixes().is_empty());
+    }
+
+    #[test]
+    fn test_remove_keeps_input_order() {
+        let mut trie = ReduceTrie::incremental();
+        trie.extend(vec![
+            net("10.2.0.0/16"),
+            net("10.0.0.0/8"),
+            net("10.1.0.0/16"),
+        ]);
+        trie.remove(net("10.0.0.0/8"));
+
+        assert_eq!(
+            vec![net("10.2.0.0/16"), net("10.1.0.0/16")],
+            trie.get_sorted_prefixes(SortOrder::InputOrder)
+        );
+    }
+
+    #[test]
+    fn test_aggregate_lossy_excluding() {
+        let mut trie = ReduceTrie::from_prefixes(vec![
+            net("10.0.0.0/25"),
+            net("10.0.1.0/25"),
+            net("10.0.4.0/24"),
+            net("192.0.2.0/25"),
+            net("192.0.2.128/26"),
+        ]);
+        let excluded = ReduceTrie::from_prefixes(vec![net("10.0.0.128/32")]);
+
+        let added = trie.aggregate_lossy_excluding(2, &excluded);
+
+        assert_eq!(
+            vec![
+                net("10.0.0.0/25"),
+                net("10.0.1.0/25"),
+                net("10.0.4.0/24"),
+                net("192.0.2.0/24"),
+            ],
+            trie.get_all_prefixes()
+        );
+        assert_eq!(64, added);
+    }
+
+    #[test]
+    fn test_stats_from_prefixes() {
+        let mut trie = ReduceTrie::from_prefixes(vec![
+            net("10.1.0.0/16"),
+            net("10.0.0.0/8"),
+            net("10.0.0.0/8"),
+            net("10.0.0.1/32"),
+            net("192.168.1.1/32"),
+            net("192.168.1.1/32"),
+            net("2001:db8::/32"),
+        ]);
+
+        let stats = trie.stats();
+        assert_eq!(
+            (6, 2, 2),
+            (stats.ipv4.inputs, stats.ipv4.duplicates, stats.ipv4.covered)
+        );
+        assert_eq!(
+            (1, 0, 0),
+            (stats.ipv6.inputs, stats.ipv6.duplicates, stats.ipv6.covered)
+        );
+
+        // prefixes of the existing entries covered by the new ones are counted as well
+        trie.extend(vec![net("192.168.0.0/16")]);
+        assert_eq!(7, trie.stats().ipv4.inputs);
+        assert_eq!(3, trie.stats().ipv4.covered);
+    }
+
+    #[test]
+    fn test_stats_from_reader_and_union() {
+        let (mut trie, _) =
+            ReduceTrie::from_reader("10.0.0.1\n10.0.0.1\n10.0.0.0/24\n".as_bytes()).unwrap();
+        let (other, _) = ReduceTrie::from_reader("2001:db8::1\n".as_bytes()).unwrap();
+
+        assert_eq!(
+            (3, 1, 1),
+            (
+                trie.stats().ipv4.inputs,
+                trie.stats().ipv4.duplicates,
+                trie.stats().ipv4.covered
+            )
+        );
+
+        trie.union(&other);
+        assert_eq!(3, trie.stats().ipv4.inputs);
+        assert_eq!(1, trie.stats().ipv6.inputs);
+
+        trie.insert(net("10.1.0.0/16"));
+        assert_eq!(3, trie.stats().ipv4.inputs);
+    }
+
+    #[test]
+    fn test_stats_independent_of_order() {
+        let counts =
+            |stats: BuildStats| (stats.ipv4.inputs, stats.ipv4.duplicates, stats.ipv4.covered);
+
+        for (lines, expected) in [
+            (["10.0.0.0/8", "10.1.0.0/16", "10.1.0.0/16"], (3, 1, 1)),
+            (["10.0.0.1/32", "10.0.0.1/32", "10.0.0.0/8"], (3, 1, 1)),
+        ] {
+            let mut reversed = lines;
+            reversed.reverse();
+
+            for lines in [lines, reversed] {
+                let (trie, _) = ReduceTrie::from_reader(lines.join("\n").as_bytes()).unwrap();
+                assert_eq!(expected, counts(trie.stats()), "{lines:?}");
+
+                let trie = ReduceTrie::from_prefixes(lines.iter().map(|l| net(l)).collect());
+                assert_eq!(expected, counts(trie.stats()), "{lines:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_stats_across_tries() {
+        let (a, _) =
+            ReduceTrie::from_reader("10.0.0.0/8\n10.1.0.0/16\n10.1.0.0/16\n".as_bytes()).unwrap();
+        let (b, _) =
+            ReduceTrie::from_reader("10.2.0.0/16\n10.0.0.0/8\n192.168.0.1\n".as_bytes()).unwrap();
+
+        let mut union = a.clone();
+        union.union(&b);
+        let stats = union.stats().ipv4;
+        assert_eq!((6, 2, 2), (stats.inputs, stats.duplicates, stats.covered));
+        assert_eq!(
+            stats.inputs - stats.duplicates - stats.covered,
+            union.iter().count()
+        );
+
+        let mut intersection = a.clone();
+        intersection.intersect(&b);
+        let stats = intersection.stats().ipv4;
+        assert_eq!(
+            stats.inputs - stats.duplicates - stats.covered,
+            intersection.iter().count()
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt;
+
+use crate::cidr::ParseError;
+
+/// A line of the input which could not be parsed into a prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidLine {
+    /// Line number in the input, starting at 1
+    pub line_number: usize,
+    /// Raw text of the line
+    pub text: String,
+    /// Reason why the line was rejected
+    pub error: ParseError,
+}
+
+impl fmt::Display for InvalidLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: {}: {:?}",
+            self.line_number, self.error, self.text
+        )
+    }
+}
+
+/// Report of the lines rejected while parsing the input. Prefixes with host bits set are only
+/// rejected with `Normalization::Reject`, the default keeps them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseReport {
+    pub invalid: Vec<InvalidLine>,
+    /// Number of prefixes read from the input, ranges count with the prefixes they were
+    /// decomposed into
+    pub prefixes: usize,
+}
+
+impl ParseReport {
+    /// Returns `true` if no line was rejected.
+    pub fn is_empty(&self) -> bool {
+        self.invalid.is_empty()
+    }
+
+    /// Adds the invalid lines and prefixes of another report (e.g. of another input file).
+    pub fn merge(&mut self, other: ParseReport) {
+        self.invalid.extend(other.invalid);
+        self.prefixes += other.prefixes;
+    }
+
+    pub(crate) fn reject(&mut self, line_number: usize, text: &str, error: ParseError) {
+        self.invalid.push(InvalidLine {
+            line_number,
+            text: text.to_string(),
+            error,
+        });
+    }
+}
@@ -0,0 +1,296 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use ipnet::IpNet;
+use net_reduce::Explanation;
+
+/// Options of the router prefix list output formats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouterOptions {
+    /// Name of the prefix lists, suffixed with `_v4`/`_v6`
+    pub name: String,
+    /// Increment of the sequence numbers (Cisco IOS and FRR)
+    pub seq_step: u32,
+}
+
+impl Default for RouterOptions {
+    fn default() -> Self {
+        RouterOptions {
+            name: "net_reduce".to_string(),
+            seq_step: 5,
+        }
+    }
+}
+
+/// A prefix of a prefix list, optionally matching more specific prefixes with a length between
+/// `ge` and `le`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub prefix: IpNet,
+    pub ge: Option<u8>,
+    pub le: Option<u8>,
+}
+
+impl Entry {
+    /// Returns the entries for the prefixes, without modifiers.
+    pub fn from_strings(prefixes: &[String]) -> Result<Vec<Entry>> {
+        prefixes
+            .iter()
+            .map(|prefix| {
+                let prefix = prefix
+                    .parse()
+                    .with_context(|| format!("invalid prefix {prefix}"))?;
+
+                Ok(Entry {
+                    prefix,
+                    ge: None,
+                    le: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the entry for the explained prefix with modifiers matching the lengths of the
+    /// more specific input prefixes it accounts for (e.g. `10.0.0.0/16 ge 24 le 24` for a /16
+    /// aggregated from /24 prefixes).
+    pub fn from_explanation(explanation: &Explanation) -> Entry {
+        let len = explanation.prefix.prefix_len();
+        let lens = explanation
+            .inputs
+            .iter()
+            .map(|(input, _)| input.prefix_len())
+            .filter(|input_len| *input_len > len);
+
+        let (ge, le) = lens.fold(
+            (None, None),
+            |(ge, le): (Option<u8>, Option<u8>), input_len| {
+                (
+                    Some(ge.map_or(input_len, |ge| ge.min(input_len))),
+                    Some(le.map_or(input_len, |le| le.max(input_len))),
+                )
+            },
+        );
+
+        // the prefix itself is matched as well if it was part of the input
+        let exact = explanation
+            .inputs
+            .iter()
+            .any(|(input, _)| input.prefix_len() == len);
+
+        Entry {
+            prefix: explanation.prefix,
+            ge: ge.filter(|_| !exact),
+            le,
+        }
+    }
+
+    fn is_ipv4(&self) -> bool {
+        matches!(self.prefix, IpNet::V4(_))
+    }
+}
+
+/// Splits the entries into IPv4 and IPv6 entries.
+fn by_family(entries: &[Entry]) -> [(&'static str, Vec<&Entry>); 2] {
+    let (ipv4, ipv6) = entries.iter().partition(|entry| entry.is_ipv4());
+
+    [("v4", ipv4), ("v6", ipv6)]
+}
+
+/// Writes `ip prefix-list` and `ipv6 prefix-list` statements (Cisco IOS and FRR), replacing
+/// existing lists of the same name.
+pub fn write_cisco<W: Write>(entries: &[Entry], options: &RouterOptions, mut w: W) -> Result<()> {
+    for (suffix, entries) in by_family(entries) {
+        if entries.is_empty() {
+            continue;
+        }
+
+        let command = if suffix == "v4" { "ip" } else { "ipv6" };
+        let name = format!("{}_{suffix}", options.name);
+
+        writeln!(w, "no {command} prefix-list {name}")?;
+        for (idx, entry) in entries.iter().enumerate() {
+            let seq = (idx as u64 + 1) * u64::from(options.seq_step);
+            write!(
+                w,
+                "{command} prefix-list {name} seq {seq} permit {}",
+                entry.prefix
+            )?;
+            if let Some(ge) = entry.ge {
+                write!(w, " ge {ge}")?;
+            }
+            if let Some(le) = entry.le {
+                write!(w, " le {le}")?;
+            }
+            writeln!(w)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes Junos `set` commands for a `policy-options prefix-list` per family, replacing
+/// existing lists of the same name. Prefix lists can not match more specific prefixes, so a
+/// `route-filter-list` is written instead if any entry has modifiers.
+pub fn write_juniper<W: Write>(entries: &[Entry], options: &RouterOptions, mut w: W) -> Result<()> {
+    for (suffix, entries) in by_family(entries) {
+        if entries.is_empty() {
+            continue;
+        }
+
+        let name = format!("{}_{suffix}", options.name);
+        let kind = match entries.iter().any(|e| e.ge.is_some() || e.le.is_some()) {
+            true => "route-filter-list",
+            false => "prefix-list",
+        };
+
+        writeln!(w, "delete policy-options {kind} {name}")?;
+        for entry in entries {
+            write!(w, "set policy-options {kind} {name} {}", entry.prefix)?;
+            if kind == "route-filter-list" {
+                let len = entry.prefix.prefix_len();
+                match (entry.ge, entry.le) {
+                    (None, None) => write!(w, " exact")?,
+                    (None, Some(le)) => write!(w, " upto /{le}")?,
+                    (Some(ge), le) => {
+                        write!(w, " prefix-length-range /{ge}-/{}", le.unwrap_or(len))?
+                    }
+                }
+            }
+            writeln!(w)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a BIRD `define` statement with a prefix set per family.
+pub fn write_bird<W: Write>(entries: &[Entry], options: &RouterOptions, mut w: W) -> Result<()> {
+    for (suffix, entries) in by_family(entries) {
+        let items: Vec<String> = entries
+            .iter()
+            .map(|entry| match (entry.ge, entry.le) {
+                (None, None) => entry.prefix.to_string(),
+                (ge, le) => {
+                    let len = entry.prefix.prefix_len();
+                    let low = ge.unwrap_or(len);
+                    format!("{}{{{low},{}}}", entry.prefix, le.unwrap_or(low))
+                }
+            })
+            .collect();
+
+        if items.is_empty() {
+            writeln!(w, "define {}_{suffix} = [];", options.name)?;
+        } else {
+            writeln!(w, "define {}_{suffix} = [", options.name)?;
+            writeln!(w, "\t{}", items.join(",\n\t"))?;
+            writeln!(w, "];")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use net_reduce::Origin;
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            Entry {
+                prefix: "10.0.0.0/16".parse().unwrap(),
+                ge: Some(24),
+                le: Some(24),
+            },
+            Entry {
+                prefix: "192.0.2.0/24".parse().unwrap(),
+                ge: None,
+                le: Some(32),
+            },
+            Entry {
+                prefix: "2001:db8::/32".parse().unwrap(),
+                ge: None,
+                le: None,
+            },
+        ]
+    }
+
+    fn write(f: impl FnOnce(&[Entry], &RouterOptions, &mut Vec<u8>) -> Result<()>) -> String {
+        let options = RouterOptions {
+            name: "peers".to_string(),
+            seq_step: 10,
+        };
+        let mut buffer = Vec::new();
+        f(&entries(), &options, &mut buffer).unwrap();
+
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_entry_from_explanation() {
+        let explanation = |prefix: &str, inputs: &[&str]| Explanation {
+            prefix: prefix.parse().unwrap(),
+            inputs: inputs
+                .iter()
+                .map(|input| {
+                    let origin = Origin {
+                        source: None,
+                        line_number: 1,
+                        annotation: None,
+                    };
+                    (input.parse().unwrap(), origin)
+                })
+                .collect(),
+        };
+        let modifiers = |e: &Explanation| {
+            let entry = Entry::from_explanation(e);
+            (entry.ge, entry.le)
+        };
+
+        assert_eq!(
+            (Some(24), Some(24)),
+            modifiers(&explanation("10.0.0.0/23", &["10.0.0.0/24", "10.0.1.0/24"]))
+        );
+        assert_eq!(
+            (None, Some(32)),
+            modifiers(&explanation("10.0.0.0/8", &["10.0.0.0/8", "10.0.0.1/32"]))
+        );
+        assert_eq!(
+            (None, None),
+            modifiers(&explanation("10.0.0.128/25", &["10.0.0.0/24"]))
+        );
+    }
+
+    #[test]
+    fn test_write_cisco() {
+        assert_eq!(
+            "no ip prefix-list peers_v4\n\
+             ip prefix-list peers_v4 seq 10 permit 10.0.0.0/16 ge 24 le 24\n\
+             ip prefix-list peers_v4 seq 20 permit 192.0.2.0/24 le 32\n\
+             no ipv6 prefix-list peers_v6\n\
+             ipv6 prefix-list peers_v6 seq 10 permit 2001:db8::/32\n",
+            write(|e, o, w| write_cisco(e, o, w))
+        );
+    }
+
+    #[test]
+    fn test_write_juniper() {
+        assert_eq!(
+            "delete policy-options route-filter-list peers_v4\n\
+             set policy-options route-filter-list peers_v4 10.0.0.0/16 prefix-length-range /24-/24\n\
+             set policy-options route-filter-list peers_v4 192.0.2.0/24 upto /32\n\
+             delete policy-options prefix-list peers_v6\n\
+             set policy-options prefix-list peers_v6 2001:db8::/32\n",
+            write(|e, o, w| write_juniper(e, o, w))
+        );
+    }
+
+    #[test]
+    fn test_write_bird() {
+        assert_eq!(
+            "define peers_v4 = [\n\t10.0.0.0/16{24,24},\n\t192.0.2.0/24{24,32}\n];\n\
+             define peers_v6 = [\n\t2001:db8::/32\n];\n",
+            write(|e, o, w| write_bird(e, o, w))
+        );
+    }
+}
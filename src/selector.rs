@@ -0,0 +1,243 @@
+use anyhow::{Result, bail};
+use serde_json::Value;
+
+/// Location of the prefixes in a JSON or YAML document, written in a subset of JSONPath, e.g.
+/// `$.prefixes[*].ip_prefix`.
+///
+/// Supported are the root `$` (optional), child names (`.name` or `['name']`), array indexes
+/// (`[0]`), wildcards (`.*` or `[*]`) selecting all elements of an array or values of an object
+/// and recursive descent (`..name`) selecting the named values at any depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Child(String),
+    Index(usize),
+    Wildcard,
+    Descendant(String),
+    /// Elements of an array, or of the `prefixes` array of an object (as written with
+    /// `--structured`), used by the default selector
+    Prefixes,
+}
+
+impl Selector {
+    /// Returns the values of the document matching the selector. Array elements are returned in
+    /// order, object values in the order of their keys.
+    pub fn select<'a>(&self, document: &'a Value) -> Vec<&'a Value> {
+        let mut values = vec![document];
+
+        for step in &self.steps {
+            values = values
+                .into_iter()
+                .flat_map(|value| step.apply(value))
+                .collect();
+        }
+
+        values
+    }
+}
+
+impl Default for Selector {
+    /// Selects the elements of an array at the root of the document, or of the `prefixes` array
+    /// of an object at the root, as written by the JSON and YAML output formats.
+    fn default() -> Self {
+        Selector {
+            steps: vec![Step::Prefixes],
+        }
+    }
+}
+
+impl Step {
+    fn apply<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        match (self, value) {
+            (Step::Child(name), Value::Object(map)) => map.get(name).into_iter().collect(),
+            (Step::Index(index), Value::Array(values)) => values.get(*index).into_iter().collect(),
+            (Step::Wildcard, Value::Array(values)) => values.iter().collect(),
+            (Step::Wildcard, Value::Object(map)) => map.values().collect(),
+            (Step::Prefixes, Value::Object(map)) => match map.get("prefixes") {
+                Some(Value::Array(values)) => values.iter().collect(),
+                _ => map.values().collect(),
+            },
+            (Step::Prefixes, _) => Step::Wildcard.apply(value),
+            (Step::Descendant(name), _) => {
+                let mut result = Vec::new();
+                descendants(value, name, &mut result);
+                result
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn descendants<'a>(value: &'a Value, name: &str, result: &mut Vec<&'a Value>) {
+    let children: Vec<&Value> = match value {
+        Value::Object(map) => {
+            if let Some(value) = map.get(name) {
+                result.push(value);
+            }
+            map.values().collect()
+        }
+        Value::Array(values) => values.iter().collect(),
+        _ => return,
+    };
+
+    for child in children {
+        descendants(child, name, result);
+    }
+}
+
+impl std::str::FromStr for Selector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut rest = s.trim().strip_prefix('$').unwrap_or(s.trim());
+        let mut steps = Vec::new();
+
+        // a leading name without dot, e.g. `prefixes[*]`
+        if !rest.is_empty() && !rest.starts_with(['.', '[']) {
+            let (name, tail) = split_name(rest);
+            steps.push(Step::Child(name.to_string()));
+            rest = tail;
+        }
+
+        while !rest.is_empty() {
+            if let Some(tail) = rest.strip_prefix("..") {
+                let (name, tail) = split_name(tail);
+                if name.is_empty() || name == "*" {
+                    bail!("Invalid selector {s}: expected name after ..");
+                }
+                steps.push(Step::Descendant(name.to_string()));
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix('.') {
+                let (name, tail) = split_name(tail);
+                steps.push(match name {
+                    "" => bail!("Invalid selector {s}: expected name after ."),
+                    "*" => Step::Wildcard,
+                    name => Step::Child(name.to_string()),
+                });
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix('[') {
+                let Some((inner, tail)) = tail.split_once(']') else {
+                    bail!("Invalid selector {s}: missing ]");
+                };
+                steps.push(parse_bracket(inner.trim()).ok_or_else(|| {
+                    anyhow::anyhow!("Invalid selector {s}: unexpected [{inner}]")
+                })?);
+                rest = tail;
+            } else {
+                bail!("Invalid selector {s}: unexpected {rest}");
+            }
+        }
+
+        Ok(Selector { steps })
+    }
+}
+
+fn split_name(s: &str) -> (&str, &str) {
+    s.split_at(s.find(['.', '[']).unwrap_or(s.len()))
+}
+
+fn parse_bracket(inner: &str) -> Option<Step> {
+    if inner == "*" {
+        return Some(Step::Wildcard);
+    }
+
+    if let Ok(index) = inner.parse() {
+        return Some(Step::Index(index));
+    }
+
+    let name = inner
+        .strip_prefix('\'')
+        .and_then(|n| n.strip_suffix('\''))
+        .or_else(|| inner.strip_prefix('"').and_then(|n| n.strip_suffix('"')))?;
+
+    Some(Step::Child(name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn select(selector: &str, document: &Value) -> Vec<Value> {
+        let selector: Selector = selector.parse().unwrap();
+        selector.select(document).into_iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_select_children() {
+        let document = json!({
+            "syncToken": "1",
+            "prefixes": [
+                {"ip_prefix": "192.0.2.0/24", "region": "eu-west-1"},
+                {"ip_prefix": "198.51.100.0/24", "region": "us-east-1"},
+            ],
+        });
+
+        assert_eq!(
+            vec![json!("192.0.2.0/24"), json!("198.51.100.0/24")],
+            select("$.prefixes[*].ip_prefix", &document)
+        );
+        assert_eq!(
+            vec![json!("198.51.100.0/24")],
+            select("prefixes[1]['ip_prefix']", &document)
+        );
+        assert_eq!(vec![json!("1")], select("$.syncToken", &document));
+        assert!(select("$.missing[*]", &document).is_empty());
+    }
+
+    #[test]
+    fn test_select_descendants() {
+        let document = json!({
+            "v4": [{"prefix": "192.0.2.0/24"}],
+            "v6": {"nested": {"prefix": "2001:db8::/32"}},
+        });
+
+        assert_eq!(
+            vec![json!("192.0.2.0/24"), json!("2001:db8::/32")],
+            select("$..prefix", &document)
+        );
+    }
+
+    #[test]
+    fn test_default_selects_root_array() {
+        let document = json!(["10.0.0.0/8", "192.0.2.1/32"]);
+
+        assert_eq!(
+            vec![json!("10.0.0.0/8"), json!("192.0.2.1/32")],
+            Selector::default()
+                .select(&document)
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_default_selects_structured_prefixes() {
+        let document = json!({
+            "prefixes": [{"prefix": "10.0.0.0/8", "family": "ipv4"}],
+            "summary": {"inputs": 1, "invalid": 0, "outputs": 1},
+        });
+
+        assert_eq!(
+            vec![json!({"prefix": "10.0.0.0/8", "family": "ipv4"})],
+            Selector::default()
+                .select(&document)
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        assert!("$.".parse::<Selector>().is_err());
+        assert!("$[foo]".parse::<Selector>().is_err());
+        assert!("$[0".parse::<Selector>().is_err());
+        assert!("$..*".parse::<Selector>().is_err());
+    }
+}
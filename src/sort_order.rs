@@ -0,0 +1,141 @@
+use ipnet::IpNet;
+use rayon::prelude::*;
+
+/// Order in which the prefixes are returned after reduction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// No particular order (per IP family, IPv4 first: trie prefixes by address, then hosts),
+    /// avoids the cost of sorting
+    None,
+    /// Sorted by IP family, network address and prefix length
+    #[default]
+    Address,
+    /// Sorted by IP family, prefix length and network address
+    PrefixLen,
+    /// Sorted by the first occurrence of the prefix in the input
+    InputOrder,
+}
+
+impl std::str::FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(SortOrder::None),
+            "address" => Ok(SortOrder::Address),
+            "prefix-len" => Ok(SortOrder::PrefixLen),
+            "input-order" => Ok(SortOrder::InputOrder),
+            _ => Err(format!("Unknown sort order: {s}")),
+        }
+    }
+}
+
+/// Sorts prefixes accompanied by their sequence numbers in the given order.
+pub fn sort(entries: &mut [(IpNet, usize)], order: SortOrder) {
+    match order {
+        SortOrder::None => {}
+        SortOrder::Address => {
+            entries.par_sort_unstable_by_key(|(p, _)| (p.network(), p.prefix_len()))
+        }
+        SortOrder::PrefixLen => entries.par_sort_unstable_by_key(|(p, _)| {
+            (p.network().is_ipv6(), p.prefix_len(), p.network())
+        }),
+        SortOrder::InputOrder => {
+            entries.par_sort_unstable_by_key(|(p, seq)| (*seq, p.network(), p.prefix_len()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn entries() -> Vec<(IpNet, usize)> {
+        vec![
+            ("2001:db8::/32".parse().unwrap(), 0),
+            ("192.168.1.1/32".parse().unwrap(), 1),
+            ("10.0.0.0/8".parse().unwrap(), 3),
+            ("10.0.0.0/7".parse().unwrap(), 2),
+            ("9.0.0.0/16".parse().unwrap(), 4),
+        ]
+    }
+
+    fn sorted(order: SortOrder) -> Vec<String> {
+        let mut entries = entries();
+        sort(&mut entries, order);
+
+        entries.iter().map(|(p, _)| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sort_none() {
+        assert_eq!(
+            vec![
+                "2001:db8::/32",
+                "192.168.1.1/32",
+                "10.0.0.0/8",
+                "10.0.0.0/7",
+                "9.0.0.0/16"
+            ],
+            sorted(SortOrder::None)
+        );
+    }
+
+    #[test]
+    fn test_sort_address() {
+        assert_eq!(
+            vec![
+                "9.0.0.0/16",
+                "10.0.0.0/7",
+                "10.0.0.0/8",
+                "192.168.1.1/32",
+                "2001:db8::/32"
+            ],
+            sorted(SortOrder::Address)
+        );
+    }
+
+    #[test]
+    fn test_sort_prefix_len() {
+        assert_eq!(
+            vec![
+                "10.0.0.0/7",
+                "10.0.0.0/8",
+                "9.0.0.0/16",
+                "192.168.1.1/32",
+                "2001:db8::/32"
+            ],
+            sorted(SortOrder::PrefixLen)
+        );
+    }
+
+    #[test]
+    fn test_sort_input_order() {
+        assert_eq!(
+            vec![
+                "2001:db8::/32",
+                "192.168.1.1/32",
+                "10.0.0.0/7",
+                "10.0.0.0/8",
+                "9.0.0.0/16"
+            ],
+            sorted(SortOrder::InputOrder)
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(SortOrder::from_str("none"), Ok(SortOrder::None));
+        assert_eq!(SortOrder::from_str("Address"), Ok(SortOrder::Address));
+        assert_eq!(SortOrder::from_str("prefix-len"), Ok(SortOrder::PrefixLen));
+        assert_eq!(
+            SortOrder::from_str("input-order"),
+            Ok(SortOrder::InputOrder)
+        );
+        assert_eq!(
+            SortOrder::from_str("random"),
+            Err("Unknown sort order: random".to_string())
+        );
+    }
+}
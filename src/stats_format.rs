@@ -0,0 +1,140 @@
+use std::io::Write;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use ipnet::IpNet;
+use net_reduce::{BuildStats, FamilyStats};
+use serde_json::{Map, Value, json};
+
+use crate::output_format;
+
+/// Stats format specifies how the statistics of a run are written
+#[derive(Debug, Clone, Copy)]
+pub enum StatsFormat {
+    Text,
+    Json,
+}
+
+impl StatsFormat {
+    /// Writes the statistics of building the trie, the number of invalid lines and the counts
+    /// and address space of the output prefixes per IP family to writer.
+    pub fn write<W: Write>(
+        &self,
+        stats: BuildStats,
+        invalid: usize,
+        outputs: &[IpNet],
+        mut w: W,
+    ) -> Result<()> {
+        let (ipv4, ipv6): (Vec<IpNet>, Vec<IpNet>) =
+            outputs.iter().partition(|p| matches!(p, IpNet::V4(_)));
+        let families = [("ipv4", stats.ipv4, ipv4), ("ipv6", stats.ipv6, ipv6)];
+
+        match self {
+            StatsFormat::Text => {
+                for (name, stats, outputs) in &families {
+                    writeln!(
+                        w,
+                        "{name}: {} inputs, {} duplicates, {} covered, {} outputs ({} addresses), \
+                         build {:.3} ms, host coverage {:.3} ms",
+                        stats.inputs,
+                        stats.duplicates,
+                        stats.covered,
+                        outputs.len(),
+                        output_format::address_count(outputs),
+                        millis(stats.build_time),
+                        millis(stats.coverage_time),
+                    )?;
+                }
+                writeln!(w, "invalid lines: {invalid}")?;
+            }
+            StatsFormat::Json => {
+                let mut document = Map::new();
+                for (name, stats, outputs) in &families {
+                    document.insert(name.to_string(), family_to_value(stats, outputs));
+                }
+                document.insert("invalid".to_string(), json!(invalid));
+
+                let json = serde_json::to_string(&document)
+                    .with_context(|| "failed to serialize statistics to JSON")?;
+                writeln!(w, "{json}").with_context(|| "failed to write statistics")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn family_to_value(stats: &FamilyStats, outputs: &[IpNet]) -> Value {
+    json!({
+        "inputs": stats.inputs,
+        "duplicates": stats.duplicates,
+        "covered": stats.covered,
+        "outputs": outputs.len(),
+        "addresses": output_format::address_count(outputs),
+        "build_ms": millis(stats.build_time),
+        "coverage_ms": millis(stats.coverage_time),
+    })
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl std::str::FromStr for StatsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(StatsFormat::Text),
+            "json" => Ok(StatsFormat::Json),
+            _ => Err(anyhow::anyhow!("Unknown stats format: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> BuildStats {
+        BuildStats {
+            ipv4: FamilyStats {
+                inputs: 5,
+                duplicates: 1,
+                covered: 2,
+                build_time: Duration::from_micros(1500),
+                coverage_time: Duration::from_micros(250),
+            },
+            ipv6: FamilyStats::default(),
+        }
+    }
+
+    fn write(format: StatsFormat) -> String {
+        let outputs = vec![
+            "10.0.0.0/8".parse().unwrap(),
+            "192.0.2.1/32".parse().unwrap(),
+        ];
+        let mut buffer = Vec::new();
+        format.write(stats(), 3, &outputs, &mut buffer).unwrap();
+
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_write_text() {
+        assert_eq!(
+            "ipv4: 5 inputs, 1 duplicates, 2 covered, 2 outputs (16777217 addresses), build 1.500 ms, host coverage 0.250 ms\n\
+             ipv6: 0 inputs, 0 duplicates, 0 covered, 0 outputs (0 addresses), build 0.000 ms, host coverage 0.000 ms\n\
+             invalid lines: 3\n",
+            write(StatsFormat::Text)
+        );
+    }
+
+    #[test]
+    fn test_write_json() {
+        assert_eq!(
+            r#"{"invalid":3,"ipv4":{"addresses":"16777217","build_ms":1.5,"coverage_ms":0.25,"covered":2,"duplicates":1,"inputs":5,"outputs":2},"ipv6":{"addresses":"0","build_ms":0.0,"coverage_ms":0.0,"covered":0,"duplicates":0,"inputs":0,"outputs":0}}"#,
+            write(StatsFormat::Json).trim()
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::fs;
+use std::io::Write;
+
+use anyhow::{Context, Result, anyhow, bail};
+use ipnet::IpNet;
+
+/// User-defined output format, read from a template file.
+///
+/// The file consists of the sections `[header]`, `[prefix]` and `[footer]`, each starting with
+/// a line containing only the section name. The header and footer are written once, the prefix
+/// section for each prefix. A file without sections is used as prefix section.
+///
+/// The prefix section may contain the placeholders `{prefix}` (e.g. `192.0.2.0/24`), `{addr}`
+/// (`192.0.2.0`), `{len}` (`24`), `{family}` (`ipv4` or `ipv6`), `{netmask}`
+/// (`255.255.255.0`), `{wildcard}` (`0.0.0.255`) and `{index}` (starting at 1). `{count}` is
+/// replaced by the number of prefixes in all sections. Literal braces are written as `{{` and
+/// `}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    header: Vec<Part>,
+    prefix: Vec<Part>,
+    footer: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Prefix,
+    Addr,
+    Len,
+    Family,
+    Netmask,
+    Wildcard,
+    Index,
+    Count,
+}
+
+impl Template {
+    /// Reads the template from a file.
+    pub fn from_file(path: &str) -> Result<Template> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("failed to read template {path}"))?;
+
+        content
+            .parse()
+            .map_err(|e| anyhow!("invalid template {path}: {e}"))
+    }
+
+    /// Writes the prefixes to writer using the template.
+    pub fn write<W: Write>(&self, prefixes: &[String], mut w: W) -> Result<()> {
+        let count = prefixes.len();
+
+        write!(w, "{}", render(&self.header, None, count))?;
+        for (idx, prefix) in prefixes.iter().enumerate() {
+            let net: IpNet = prefix
+                .parse()
+                .with_context(|| format!("invalid prefix {prefix}"))?;
+            write!(w, "{}", render(&self.prefix, Some((idx + 1, net)), count))?;
+        }
+        write!(w, "{}", render(&self.footer, None, count))?;
+
+        Ok(())
+    }
+}
+
+fn render(parts: &[Part], prefix: Option<(usize, IpNet)>, count: usize) -> String {
+    let mut result = String::new();
+
+    for part in parts {
+        let value = match (part, prefix) {
+            (Part::Text(text), _) => text.clone(),
+            (Part::Count, _) => count.to_string(),
+            (Part::Prefix, Some((_, net))) => net.to_string(),
+            (Part::Addr, Some((_, net))) => net.addr().to_string(),
+            (Part::Len, Some((_, net))) => net.prefix_len().to_string(),
+            (Part::Family, Some((_, IpNet::V4(_)))) => "ipv4".to_string(),
+            (Part::Family, Some((_, IpNet::V6(_)))) => "ipv6".to_string(),
+            (Part::Netmask, Some((_, net))) => net.netmask().to_string(),
+            (Part::Wildcard, Some((_, net))) => net.hostmask().to_string(),
+            (Part::Index, Some((idx, _))) => idx.to_string(),
+            (_, None) => unreachable!("prefix placeholders are only parsed in the prefix section"),
+        };
+        result.push_str(&value);
+    }
+
+    result
+}
+
+impl std::str::FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut sections: [Option<String>; 3] = [None, None, None];
+        let mut current: Option<usize> = None;
+        let mut leading = String::new();
+
+        for line in s.split_inclusive('\n') {
+            let section = match line.trim_end() {
+                "[header]" => Some(0),
+                "[prefix]" => Some(1),
+                "[footer]" => Some(2),
+                _ => None,
+            };
+
+            match (section, current) {
+                (Some(idx), _) if sections[idx].is_some() => {
+                    bail!("section {} is defined twice", line.trim_end())
+                }
+                (Some(idx), _) => {
+                    sections[idx] = Some(String::new());
+                    current = Some(idx);
+                }
+                (None, Some(idx)) => sections[idx].get_or_insert_default().push_str(line),
+                (None, None) => leading.push_str(line),
+            }
+        }
+
+        // without sections the whole template is written for each prefix
+        let Some(_) = current else {
+            return Ok(Template {
+                header: Vec::new(),
+                prefix: parse_parts(&leading, true)?,
+                footer: Vec::new(),
+            });
+        };
+
+        if !leading.trim().is_empty() {
+            bail!("text before the first section");
+        }
+
+        let [header, prefix, footer] = sections.map(Option::unwrap_or_default);
+
+        Ok(Template {
+            header: parse_parts(&header, false)?,
+            prefix: parse_parts(&prefix, true)?,
+            footer: parse_parts(&footer, false)?,
+        })
+    }
+}
+
+/// Splits the text of a section into text and placeholders. Placeholders of the prefix are
+/// only accepted in the prefix section.
+fn parse_parts(s: &str, prefix_section: bool) -> Result<Vec<Part>> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(c);
+                }
+                if !closed {
+                    bail!("unclosed {{, literal braces are written as {{{{");
+                }
+
+                let part = match name.as_str() {
+                    "count" => Part::Count,
+                    "prefix" => Part::Prefix,
+                    "addr" => Part::Addr,
+                    "len" => Part::Len,
+                    "family" => Part::Family,
+                    "netmask" => Part::Netmask,
+                    "wildcard" => Part::Wildcard,
+                    "index" => Part::Index,
+                    _ => bail!("unknown placeholder {{{name}}}"),
+                };
+                if !prefix_section && part != Part::Count {
+                    bail!("placeholder {{{name}}} is only allowed in the prefix section");
+                }
+
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(part);
+            }
+            '}' => bail!("unmatched }}, literal braces are written as }}}}"),
+            c => text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, prefixes: &[&str]) -> String {
+        let template: Template = template.parse().unwrap();
+        let prefixes: Vec<String> = prefixes.iter().map(|p| p.to_string()).collect();
+        let mut buffer = Vec::new();
+        template.write(&prefixes, &mut buffer).unwrap();
+
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_write_sections() {
+        let template = "[header]\ntable <blocked> {{ # {count} prefixes\n\
+                        [prefix]\n  {prefix} ({family}, {index})\n\
+                        [footer]\n}}\n";
+
+        assert_eq!(
+            "table <blocked> { # 2 prefixes\n  10.0.0.0/8 (ipv4, 1)\n  2001:db8::/32 (ipv6, 2)\n}\n",
+            render(template, &["10.0.0.0/8", "2001:db8::/32"])
+        );
+    }
+
+    #[test]
+    fn test_write_without_sections() {
+        assert_eq!(
+            "access-list 10 permit 192.0.2.0 0.0.0.255 ! 192.0.2.0/24 255.255.255.0\n",
+            render(
+                "access-list 10 permit {addr} {wildcard} ! {addr}/{len} {netmask}\n",
+                &["192.0.2.0/24"]
+            )
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let parse = |s: &str| s.parse::<Template>().unwrap_err().to_string();
+
+        assert_eq!("unknown placeholder {mask}", parse("{mask}"));
+        assert_eq!(
+            "placeholder {prefix} is only allowed in the prefix section",
+            parse("[header]\n{prefix}\n")
+        );
+        assert_eq!(
+            "section [footer] is defined twice",
+            parse("[footer]\n[footer]\n")
+        );
+        assert_eq!("text before the first section", parse("x\n[prefix]\n"));
+        assert!(parse("}").starts_with("unmatched }"));
+        assert_eq!(
+            "unclosed {, literal braces are written as {{",
+            parse("{prefix")
+        );
+    }
+}
@@ -167,6 +167,440 @@ fn test_cli_mixed_ipv4_ipv6() {
     assert!(lines.contains(&"2001:db8::/32"));
 }
 
+#[test]
+fn test_cli_aggregate() {
+    let input = "10.0.0.0/25\n10.0.0.128/25\n10.0.1.0/24\n10.0.3.1\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--aggregate"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let lines: Vec<&str> = stdout.trim().lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines.contains(&"10.0.0.0/23"));
+    assert!(lines.contains(&"10.0.3.1/32"));
+}
+
+#[test]
+fn test_cli_max_entries() {
+    let input = "10.0.0.0/24\n10.0.2.0/24\n10.0.8.0/24\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--max-entries", "2"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let lines: Vec<&str> = stdout.trim().lines().collect();
+    assert_eq!(lines, vec!["10.0.0.0/22", "10.0.8.0/24"]);
+    assert!(
+        stderr.contains("512 addresses covered in addition to the input"),
+        "Should report additional address space. stderr: {stderr}"
+    );
+}
+
+#[test]
+fn test_cli_report_invalid() {
+    let input = "192.168.1.0/24\n192,45.3.1\n10.0.0.0/33\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--report-invalid"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout.trim(), "192.168.1.0/24");
+    assert!(stderr.contains("line 2: invalid IP address: \"192,45.3.1\""));
+    assert!(stderr.contains("line 3: prefix length 33 out of range (max 32): \"10.0.0.0/33\""));
+}
+
+#[test]
+fn test_cli_strict_fails_on_invalid_input() {
+    let input = "192.168.1.0/24\n192,45.3.1\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--strict"]);
+
+    assert_ne!(
+        exit_code, 0,
+        "CLI should fail on invalid input in strict mode"
+    );
+    assert_eq!(stdout, "", "No prefixes should be written in strict mode");
+    assert!(stderr.contains("line 2: invalid IP address"));
+}
+
+#[test]
+fn test_cli_strict_with_valid_input() {
+    let input = "192.168.1.0/24\n\n192.168.1.1\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--strict"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout.trim(), "192.168.1.0/24");
+}
+
+#[test]
+fn test_cli_exclude() {
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    let mut exclude_file = NamedTempFile::new().expect("Failed to create temp file");
+    writeln!(exclude_file, "10.1.0.0/16").unwrap();
+    writeln!(exclude_file, "192.168.1.1").unwrap();
+    exclude_file.flush().unwrap();
+
+    let input = "10.0.0.0/14\n192.168.1.1\n192.168.1.2\n";
+    let (stdout, stderr, exit_code) =
+        run_cli_with_input(input, &["--exclude", exclude_file.path().to_str().unwrap()]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let mut lines: Vec<&str> = stdout.trim().lines().collect();
+    lines.sort();
+    assert_eq!(lines, vec!["10.0.0.0/16", "10.2.0.0/15", "192.168.1.2/32"]);
+}
+
+#[test]
+fn test_cli_exclude_with_extract() {
+    let exclude_file = temp_file_with_lines(&["10.1.0.0/16"]);
+    let exclude = exclude_file.path().to_str().unwrap();
+    let input = "client=10.0.0.1\nclient=10.1.0.1\n";
+
+    for extract in [
+        &["--extract-regex", r"client=(?<prefix>\S+)"][..],
+        &["--extract"],
+    ] {
+        let mut args = vec!["-x", exclude];
+        args.extend(extract);
+        let (stdout, stderr, exit_code) = run_cli_with_input(input, &args);
+
+        assert_eq!(
+            exit_code, 0,
+            "CLI should exit successfully. stderr: {stderr}"
+        );
+        assert_eq!(stdout, "10.0.0.1/32\n");
+    }
+}
+
+#[test]
+fn test_cli_exclude_with_input_format() {
+    let exclude_file = temp_file_with_lines(&["10.1.0.0/16"]);
+    let input = "name,prefix\na,10.0.0.0/16\nb,10.1.0.0/16\n";
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        input,
+        &[
+            "-i",
+            "csv",
+            "-c",
+            "prefix",
+            "-x",
+            exclude_file.path().to_str().unwrap(),
+        ],
+    );
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/16\n");
+}
+
+#[test]
+fn test_cli_exclude_with_max_entries() {
+    let exclude_file = temp_file_with_lines(&["10.0.0.128/25"]);
+
+    let input = "10.0.0.0/25\n10.0.1.0/25\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        input,
+        &[
+            "--exclude",
+            exclude_file.path().to_str().unwrap(),
+            "--max-entries",
+            "1",
+        ],
+    );
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/25\n10.0.1.0/25\n");
+    assert!(stderr.contains("2 entries left, merging further would cover excluded prefixes"));
+}
+
+#[test]
+fn test_cli_max_entries_below_family_count() {
+    let input = "10.0.0.0/24\n192.168.0.0/24\n2001:db8::/32\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--max-entries", "0"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "0.0.0.0/0\n2001:db8::/32\n");
+    assert!(stderr.contains("2 entries left, each IP family keeps at least one prefix"));
+    assert!(!stderr.contains("excluded"));
+}
+
+#[test]
+fn test_cli_stdin_read_once() {
+    let (stdout, stderr, exit_code) = run_cli_with_input("10.0.0.0/8\n", &["-", "-"]);
+    assert_ne!(exit_code, 0);
+    assert!(stdout.is_empty());
+    assert!(stderr.contains("stdin (-) can only be read once"));
+
+    let (_, stderr, exit_code) = run_cli_with_input("10.0.0.0/8\n", &["--exclude", "-"]);
+    assert_ne!(exit_code, 0);
+    assert!(stderr.contains("stdin (-) can only be read once"));
+}
+
+#[test]
+fn test_cli_file_named_like_subcommand() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("union"), "10.0.0.0/8\n").unwrap();
+
+    let mut cmd = std::process::Command::new(env!("CARGO_BIN_EXE_net-reduce"));
+    let output = cmd
+        .current_dir(dir.path())
+        .args(["--file", "union"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(str::from_utf8(&output.stdout).unwrap(), "10.0.0.0/8\n");
+}
+
+fn temp_file_with_lines(lines: &[&str]) -> tempfile::NamedTempFile {
+    let mut file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    for line in lines {
+        writeln!(file, "{line}").unwrap();
+    }
+    file.flush().unwrap();
+
+    file
+}
+
+#[test]
+fn test_cli_intersect() {
+    let a = temp_file_with_lines(&["10.0.0.0/8", "192.168.1.1", "2001:db8::/32"]);
+    let b = temp_file_with_lines(&["10.1.0.0/16", "10.2.0.0/16", "192.168.1.2"]);
+    let c = temp_file_with_lines(&["10.0.0.0/14"]);
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        "",
+        &[
+            "intersect",
+            a.path().to_str().unwrap(),
+            b.path().to_str().unwrap(),
+            c.path().to_str().unwrap(),
+        ],
+    );
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let lines: Vec<&str> = stdout.trim().lines().collect();
+    assert_eq!(lines, vec!["10.1.0.0/16", "10.2.0.0/16"]);
+}
+
+#[test]
+fn test_cli_intersect_requires_two_files() {
+    let a = temp_file_with_lines(&["10.0.0.0/8"]);
+
+    let (_stdout, stderr, exit_code) =
+        run_cli_with_input("", &["intersect", a.path().to_str().unwrap()]);
+
+    assert_ne!(exit_code, 0, "CLI should fail with a single file");
+    assert!(!stderr.is_empty(), "Should have error message");
+}
+
+#[test]
+fn test_cli_union() {
+    let a = temp_file_with_lines(&["10.0.0.0/25", "192.168.1.1"]);
+    let b = temp_file_with_lines(&["10.0.0.128/25", "192.168.1.0/24", "2001:db8::1"]);
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        "",
+        &[
+            "union",
+            a.path().to_str().unwrap(),
+            b.path().to_str().unwrap(),
+            "-o",
+            "json",
+        ],
+    );
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(stdout.trim()).expect("Output should be valid JSON");
+    assert_eq!(
+        json,
+        serde_json::json!(["10.0.0.0/24", "192.168.1.0/24", "2001:db8::1/128"])
+    );
+}
+
+#[test]
+fn test_cli_union_requires_two_files() {
+    let a = temp_file_with_lines(&["10.0.0.0/8"]);
+
+    let (_stdout, stderr, exit_code) =
+        run_cli_with_input("", &["union", a.path().to_str().unwrap()]);
+
+    assert_ne!(exit_code, 0, "CLI should fail with a single file");
+    assert!(!stderr.is_empty(), "Should have error message");
+}
+
+#[test]
+fn test_cli_diff_rejects_unsupported_flags() {
+    let old = temp_file_with_lines(&["10.0.0.0/24"]);
+    let new = temp_file_with_lines(&["10.0.0.0/23"]);
+    let files = [old.path().to_str().unwrap(), new.path().to_str().unwrap()];
+
+    for flags in [
+        &["--max-entries", "1"][..],
+        &["--explain"],
+        &["--stats"],
+        &["--structured"],
+    ] {
+        let mut args = vec!["diff", files[0], files[1]];
+        args.extend(flags);
+        let (stdout, stderr, exit_code) = run_cli_with_input("", &args);
+
+        assert_ne!(exit_code, 0, "diff should fail with {flags:?}");
+        assert!(stdout.is_empty());
+        assert!(stderr.contains(&format!("{} is not supported by diff", flags[0])));
+    }
+}
+
+#[test]
+fn test_cli_diff() {
+    let old = temp_file_with_lines(&["10.0.0.0/24", "192.168.1.1", "2001:db8::/64"]);
+    let new = temp_file_with_lines(&["10.0.0.0/23", "2001:db8::/64"]);
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        "",
+        &[
+            "diff",
+            old.path().to_str().unwrap(),
+            new.path().to_str().unwrap(),
+        ],
+    );
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let lines: Vec<&str> = stdout.trim().lines().collect();
+    assert_eq!(lines, vec!["+10.0.1.0/24", "-192.168.1.1/32"]);
+}
+
+#[test]
+fn test_cli_diff_json_output_format() {
+    let old = temp_file_with_lines(&["10.0.0.0/24"]);
+    let new = temp_file_with_lines(&["10.0.0.0/25"]);
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        "",
+        &[
+            "diff",
+            old.path().to_str().unwrap(),
+            new.path().to_str().unwrap(),
+            "-o",
+            "json",
+        ],
+    );
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(stdout.trim()).expect("Output should be valid JSON");
+    assert_eq!(
+        json,
+        serde_json::json!({"added": [], "removed": ["10.0.0.128/25"]})
+    );
+}
+
+#[test]
+fn test_cli_sorted_output() {
+    let input = "2001:db8::1\n192.168.1.1\n10.0.0.0/8\n10.0.0.1\n172.16.0.0/12\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &[]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let lines: Vec<&str> = stdout.trim().lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "10.0.0.0/8",
+            "172.16.0.0/12",
+            "192.168.1.1/32",
+            "2001:db8::1/128"
+        ]
+    );
+}
+
+#[test]
+fn test_cli_sort_input_order() {
+    let input = "2001:db8::1\n192.168.1.1\n10.0.0.0/8\n10.0.0.1\n172.16.0.0/12\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--sort", "input-order"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let lines: Vec<&str> = stdout.trim().lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "2001:db8::1/128",
+            "192.168.1.1/32",
+            "10.0.0.0/8",
+            "172.16.0.0/12"
+        ]
+    );
+}
+
+#[test]
+fn test_cli_sort_prefix_len() {
+    let input = "192.168.1.1\n10.0.0.0/8\n172.16.0.0/12\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["-s", "prefix-len"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let lines: Vec<&str> = stdout.trim().lines().collect();
+    assert_eq!(lines, vec!["10.0.0.0/8", "172.16.0.0/12", "192.168.1.1/32"]);
+}
+
+#[test]
+fn test_cli_invalid_sort_order() {
+    let (_stdout, stderr, exit_code) = run_cli_with_input("10.0.0.0/8\n", &["--sort", "random"]);
+
+    assert_ne!(exit_code, 0, "CLI should fail with invalid sort order");
+    assert!(!stderr.is_empty(), "Should have error message");
+}
+
 #[test]
 fn test_cli_nonexistent_file() {
     let (_stdout, stderr, exit_code) = run_cli_with_input("", &["-f", "/nonexistent/file.txt"]);
@@ -222,3 +656,589 @@ fn test_cli_version_flag() {
         "Version should contain program name"
     );
 }
+
+#[test]
+fn test_cli_explain() {
+    let input = "10.1.0.0/16\n10.0.0.0/8\n192.168.1.1\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--explain"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(
+        stdout,
+        "10.0.0.0/8\n  10.1.0.0/16 (line 1)\n  10.0.0.0/8 (line 2)\n192.168.1.1/32\n  192.168.1.1/32 (line 3)\n"
+    );
+}
+
+#[test]
+fn test_cli_explain_json_with_files() {
+    let a = temp_file_with_lines(&["10.0.0.0/25", "192.168.1.1"]);
+    let b = temp_file_with_lines(&["10.0.0.128/25"]);
+    let a_path = a.path().to_str().unwrap();
+    let b_path = b.path().to_str().unwrap();
+
+    let (stdout, stderr, exit_code) =
+        run_cli_with_input("", &["union", a_path, b_path, "--explain", "-o", "json"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(stdout.trim()).expect("Output should be valid JSON");
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {
+                "prefix": "10.0.0.0/24",
+                "inputs": [
+                    {"prefix": "10.0.0.0/25", "source": a_path, "line": 1},
+                    {"prefix": "10.0.0.128/25", "source": b_path, "line": 1},
+                ],
+            },
+            {
+                "prefix": "192.168.1.1/32",
+                "inputs": [{"prefix": "192.168.1.1/32", "source": a_path, "line": 2}],
+            },
+        ])
+    );
+}
+
+#[test]
+fn test_cli_multiple_files() {
+    let a = temp_file_with_lines(&["10.0.0.0/8", "192.168.1.1"]);
+    let b = temp_file_with_lines(&["10.1.0.0/16", "172.16.0.0/12"]);
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        "192.168.0.0/16\n",
+        &[
+            "-f",
+            a.path().to_str().unwrap(),
+            b.path().to_str().unwrap(),
+            "-",
+        ],
+    );
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/8\n172.16.0.0/12\n192.168.0.0/16\n");
+}
+
+#[test]
+fn test_cli_multiple_files_report_source() {
+    let a = temp_file_with_lines(&["10.0.0.0/8"]);
+    let b = temp_file_with_lines(&["10.1.0.0/16", "invalid"]);
+    let b_path = b.path().to_str().unwrap();
+
+    let (_, stderr, exit_code) =
+        run_cli_with_input("", &[a.path().to_str().unwrap(), b_path, "--strict"]);
+
+    assert_eq!(exit_code, 1);
+    assert!(stderr.contains(&format!(
+        "{b_path}: line 2: invalid IP address: \"invalid\""
+    )));
+}
+
+#[test]
+fn test_cli_glob() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.txt"), "10.0.0.0/25\n").unwrap();
+    std::fs::write(dir.path().join("b.txt"), "10.0.0.128/25\n").unwrap();
+    std::fs::write(dir.path().join("c.csv"), "192.168.0.0/16\n").unwrap();
+    let pattern = format!("{}/*.txt", dir.path().to_str().unwrap());
+
+    let (stdout, stderr, exit_code) = run_cli_with_input("", &[&pattern, "--aggregate"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/24\n");
+}
+
+#[test]
+fn test_cli_with_source() {
+    let a = temp_file_with_lines(&["10.0.0.0/8", "192.168.1.1"]);
+    let b = temp_file_with_lines(&["10.1.0.0/16"]);
+    let a_path = a.path().to_str().unwrap();
+    let b_path = b.path().to_str().unwrap();
+
+    let (stdout, stderr, exit_code) =
+        run_cli_with_input("", &[a_path, b_path, "--with-source", "-o", "json"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(stdout.trim()).expect("Output should be valid JSON");
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {"prefix": "10.0.0.0/8", "sources": [a_path, b_path]},
+            {"prefix": "192.168.1.1/32", "sources": [a_path]},
+        ])
+    );
+}
+
+#[test]
+fn test_cli_comments_and_annotations() {
+    let input = "# private\n10.0.0.0/8 # RFC1918\n; lab\n10.1.0.0/16 // lab\n192.168.1.1\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--strict"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/8\n192.168.1.1/32\n");
+
+    let (stdout, stderr, exit_code) =
+        run_cli_with_input(input, &["--with-annotations", "-o", "json"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(stdout.trim()).expect("Output should be valid JSON");
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {"prefix": "10.0.0.0/8", "annotations": ["RFC1918", "lab"]},
+            {"prefix": "192.168.1.1/32", "annotations": []},
+        ])
+    );
+}
+
+#[test]
+fn test_cli_ranges() {
+    let input =
+        "192.0.2.10-192.0.2.20\n192.0.2.12/30\n2001:db8::1 - 2001:db8::3\n192.0.2.20-192.0.2.10\n";
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--report-invalid"]);
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(
+        stdout,
+        "192.0.2.10/31\n192.0.2.12/30\n192.0.2.16/30\n192.0.2.20/32\n2001:db8::1/128\n2001:db8::2/127\n"
+    );
+    assert!(stderr.contains("line 4: invalid address range: \"192.0.2.20-192.0.2.10\""));
+}
+
+#[test]
+fn test_cli_normalize() {
+    let input = "10.1.2.3/8\n192.168.1.1/24\n";
+
+    let (stdout, _, exit_code) = run_cli_with_input(input, &[]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(stdout, "10.1.2.3/8\n192.168.1.1/24\n");
+
+    let (stdout, _, exit_code) = run_cli_with_input(input, &["--normalize", "truncate"]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(stdout, "10.0.0.0/8\n192.168.1.0/24\n");
+
+    let (_, stderr, exit_code) = run_cli_with_input(input, &["--normalize", "reject", "--strict"]);
+    assert_eq!(exit_code, 1);
+    assert!(stderr.contains("line 1: host bits set: \"10.1.2.3/8\""));
+    assert!(stderr.contains("2 invalid lines found"));
+}
+
+#[test]
+fn test_cli_ipv4_embedded() {
+    let input = "192.0.2.0/24\n::ffff:192.0.2.1\n64:ff9b::192.0.2.7\n::ffff:198.51.100.1\n";
+
+    let (stdout, _, exit_code) = run_cli_with_input(input, &["--ipv4-embedded", "mapped"]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        stdout,
+        "192.0.2.0/24\n198.51.100.1/32\n64:ff9b::c000:207/128\n"
+    );
+
+    let (stdout, _, exit_code) = run_cli_with_input(input, &["--ipv4-embedded", "nat64"]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(stdout, "192.0.2.0/24\n198.51.100.1/32\n");
+}
+
+#[test]
+fn test_cli_extract() {
+    let input = "Oct 10 13:55:36 sshd[1]: Failed password for root from 203.0.113.5 port 22\n\
+                 Oct 10 13:55:37 kernel: connection 192.0.2.1:80 -> 198.51.100.7:443\n\
+                 Oct 10 13:55:38 cron: no address here\n";
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--extract", "--strict"]);
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "192.0.2.1/32\n198.51.100.7/32\n203.0.113.5/32\n");
+
+    let (stdout, _, exit_code) = run_cli_with_input(input, &["--extract-nth", "2"]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(stdout, "198.51.100.7/32\n");
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--extract-nth", "0"]);
+    assert_ne!(exit_code, 0);
+    assert!(stdout.is_empty());
+    assert!(stderr.contains("--extract-nth"));
+}
+
+#[test]
+fn test_cli_extract_regex() {
+    let input = "src=192.0.2.1 dst=198.51.100.7\nsrc=invalid dst=192.0.2.2\nheartbeat\n";
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        input,
+        &["--extract-regex", r"src=(?<prefix>\S+)", "--report-invalid"],
+    );
+
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "192.0.2.1/32\n");
+    assert!(stderr.contains("line 2: invalid IP address: \"invalid\""));
+}
+
+#[test]
+fn test_cli_csv_input() {
+    let file = temp_file_with_lines(&[
+        "hostname,Prefix,owner",
+        "lab,10.0.0.0/8,\"Doe, Jane\"",
+        "lab-1,10.1.0.0/16,ops",
+        "\"web\nfrontend\",192.0.2.0/24,ops",
+        "db,invalid,ops",
+    ]);
+    let path = file.path().to_str().unwrap();
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        "",
+        &[
+            "--input-format",
+            "csv",
+            "--column",
+            "prefix",
+            "--report-invalid",
+            path,
+        ],
+    );
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/8\n192.0.2.0/24\n");
+    assert!(stderr.contains(&format!("{path}: line 6: invalid IP address: \"invalid\"")));
+
+    let (stdout, _, exit_code) = run_cli_with_input("", &["-i", "csv", "-c", "2", path]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(stdout, "10.0.0.0/8\n192.0.2.0/24\n");
+
+    let (_, stderr, exit_code) = run_cli_with_input("", &["-i", "csv", "-c", "address", path]);
+    assert_ne!(exit_code, 0);
+    assert!(stderr.contains("column address not found"));
+}
+
+#[test]
+fn test_cli_tsv_input() {
+    let input = "192.0.2.1\tweb\n10.0.0.0/8\tlab\n10.1.0.0/16\tlab\n";
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--input-format", "tsv"]);
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/8\n192.0.2.1/32\n");
+}
+
+#[test]
+fn test_cli_json_input() {
+    let input = r#"{"syncToken": "1", "prefixes": [
+        {"ip_prefix": "10.0.0.0/8", "region": "eu-west-1"},
+        {"ip_prefix": "10.1.0.0/16", "region": "eu-west-1"},
+        {"ip_prefix": "192.0.2.0/24", "region": "us-east-1"}
+    ]}"#;
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        input,
+        &[
+            "--input-format",
+            "json",
+            "--select",
+            "$.prefixes[*].ip_prefix",
+            "--strict",
+        ],
+    );
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/8\n192.0.2.0/24\n");
+}
+
+#[test]
+fn test_cli_reads_own_output() {
+    let input = "10.0.0.0/8\n10.1.0.0/16\n2001:db8::/32\n";
+
+    for format in ["json", "yaml"] {
+        let (output, _, exit_code) = run_cli_with_input(input, &["-o", format]);
+        assert_eq!(exit_code, 0);
+
+        let (stdout, stderr, exit_code) = run_cli_with_input(&output, &["-i", format, "--strict"]);
+        assert_eq!(
+            exit_code, 0,
+            "CLI should exit successfully. stderr: {stderr}"
+        );
+        assert_eq!(stdout, "10.0.0.0/8\n2001:db8::/32\n");
+    }
+}
+
+#[test]
+fn test_cli_firewall_input() {
+    let ipset = "create blocked hash:net family inet hashsize 1024\n\
+                 add blocked 10.0.0.0/8\n\
+                 add blocked 10.1.0.0/16 timeout 300\n\
+                 add blocked 192.0.2.0/25 nomatch\n\
+                 add blocked 192.0.2.1\n";
+    let (stdout, stderr, exit_code) =
+        run_cli_with_input(ipset, &["--input-format", "ipset", "--strict"]);
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/8\n192.0.2.1/32\n");
+
+    let nftables = "table inet filter {\n\
+                    \tset blocked_v4 {\n\
+                    \t\ttype ipv4_addr\n\
+                    \t\tflags interval\n\
+                    \t\telements = { 10.0.0.0/8, 10.1.0.0/16,\n\
+                    \t\t\t     192.0.2.0-192.0.2.255 }\n\
+                    \t}\n\
+                    }\n";
+    let (stdout, stderr, exit_code) =
+        run_cli_with_input(nftables, &["--input-format", "nftables", "--strict"]);
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/8\n192.0.2.0/24\n");
+
+    let iptables = "*filter\n\
+                    :INPUT ACCEPT [0:0]\n\
+                    -A INPUT -s 10.0.0.0/8 -j DROP\n\
+                    -A INPUT -s 10.1.0.0/16,192.0.2.1/32 -d 198.51.100.1/32 -j DROP\n\
+                    -A INPUT ! -s 203.0.113.0/24 -j DROP\n\
+                    COMMIT\n";
+    let (stdout, stderr, exit_code) =
+        run_cli_with_input(iptables, &["--input-format", "iptables", "--explain"]);
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(
+        stdout,
+        "10.0.0.0/8\n  10.0.0.0/8 (line 3)\n  10.1.0.0/16 (line 4)\n\
+         192.0.2.1/32\n  192.0.2.1/32 (line 4)\n\
+         198.51.100.1/32\n  198.51.100.1/32 (line 4)\n"
+    );
+}
+
+#[test]
+fn test_cli_firewall_output() {
+    let input = "10.0.0.0/8\n10.1.0.0/16\n2001:db8::/32\n";
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        input,
+        &[
+            "-o",
+            "nftables",
+            "--table-name",
+            "fw",
+            "--set-name",
+            "blocked",
+        ],
+    );
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert!(stdout.starts_with("add table inet fw\nadd set inet fw blocked_v4 {"));
+    assert!(stdout.contains(
+        "flush set inet fw blocked_v4\nadd element inet fw blocked_v4 {\n\t10.0.0.0/8\n}\n"
+    ));
+    assert!(stdout.contains("add set inet fw blocked_v6 { type ipv6_addr; flags interval; }\n"));
+
+    // the generated set can be read back
+    let (stdout, _, exit_code) = run_cli_with_input(&stdout, &["-i", "nftables", "--strict"]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(stdout, "10.0.0.0/8\n2001:db8::/32\n");
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["-o", "iptables"]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        stdout,
+        "# load with iptables-restore --noflush, jump to the chain with iptables -I INPUT -j blocklist\n\
+         *filter\n:blocklist - [0:0]\n-A blocklist -s 10.0.0.0/8 -j DROP\nCOMMIT\n"
+    );
+    assert!(
+        stderr.contains("1 prefixes of the other IP family skipped, use -o ip6tables for them")
+    );
+
+    let (_, stderr, exit_code) = run_cli_with_input("2001:db8::/32\n", &["-o", "ip6tables"]);
+    assert_eq!(exit_code, 0);
+    assert!(!stderr.contains("skipped"));
+
+    let (stdout, _, exit_code) = run_cli_with_input(input, &["-o", "ipset"]);
+    assert_eq!(exit_code, 0);
+    let (stdout, _, exit_code) = run_cli_with_input(&stdout, &["-i", "ipset", "--strict"]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(stdout, "10.0.0.0/8\n2001:db8::/32\n");
+}
+
+#[test]
+fn test_cli_router_output() {
+    let input = "10.0.0.0/24\n10.0.1.0/24\n192.0.2.0/24\n192.0.2.1\n2001:db8::/32\n";
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(
+        input,
+        &[
+            "-a",
+            "-o",
+            "cisco",
+            "--prefix-list-name",
+            "peers",
+            "--with-modifiers",
+        ],
+    );
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(
+        stdout,
+        "no ip prefix-list peers_v4\n\
+         ip prefix-list peers_v4 seq 5 permit 10.0.0.0/23 ge 24 le 24\n\
+         ip prefix-list peers_v4 seq 10 permit 192.0.2.0/24 le 32\n\
+         no ipv6 prefix-list peers_v6\n\
+         ipv6 prefix-list peers_v6 seq 5 permit 2001:db8::/32\n"
+    );
+
+    let (stdout, _, exit_code) = run_cli_with_input(input, &["-o", "bird"]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        stdout,
+        "define net_reduce_v4 = [\n\t10.0.0.0/24,\n\t10.0.1.0/24,\n\t192.0.2.0/24\n];\n\
+         define net_reduce_v6 = [\n\t2001:db8::/32\n];\n"
+    );
+
+    let (stdout, _, exit_code) = run_cli_with_input(input, &["-o", "juniper", "--seq-step", "10"]);
+    assert_eq!(exit_code, 0);
+    assert!(stdout.starts_with("delete policy-options prefix-list net_reduce_v4\n"));
+    assert!(stdout.contains("set policy-options prefix-list net_reduce_v6 2001:db8::/32\n"));
+
+    let (stdout, stderr, exit_code) =
+        run_cli_with_input(input, &["-o", "cisco", "--seq-step", "0"]);
+    assert_ne!(exit_code, 0);
+    assert!(stdout.is_empty());
+    assert!(stderr.contains("must be at least 1"));
+}
+
+#[test]
+fn test_cli_template() {
+    let template = temp_file_with_lines(&[
+        "[header]",
+        "! {count} prefixes",
+        "[prefix]",
+        "access-list 10 permit {addr} {wildcard}",
+    ]);
+    let input = "192.0.2.0/24\n192.0.2.1\n10.0.0.0/8\n";
+
+    let (stdout, stderr, exit_code) =
+        run_cli_with_input(input, &["--template", template.path().to_str().unwrap()]);
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(
+        stdout,
+        "! 2 prefixes\naccess-list 10 permit 10.0.0.0 0.255.255.255\n\
+         access-list 10 permit 192.0.2.0 0.0.0.255\n"
+    );
+
+    let invalid = temp_file_with_lines(&["{mask}"]);
+    let (_, stderr, exit_code) =
+        run_cli_with_input(input, &["--template", invalid.path().to_str().unwrap()]);
+    assert_ne!(exit_code, 0);
+    assert!(stderr.contains("unknown placeholder {mask}"));
+}
+
+#[test]
+fn test_cli_structured_output() {
+    let input = "10.0.0.0/24\n10.0.0.1\ninvalid\n2001:db8::1\n";
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["-o", "json", "--structured"]);
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(
+        stdout.trim(),
+        r#"{"prefixes":[{"addresses":"256","family":"ipv4","first":"10.0.0.0","last":"10.0.0.255","netmask":"255.255.255.0","network":"10.0.0.0","prefix":"10.0.0.0/24","prefix_len":24},{"addresses":"1","family":"ipv6","first":"2001:db8::1","last":"2001:db8::1","netmask":"ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff","network":"2001:db8::1","prefix":"2001:db8::1/128","prefix_len":128}],"summary":{"inputs":3,"invalid":1,"outputs":2}}"#
+    );
+
+    // the structured output can be read back
+    let (stdout, _, exit_code) = run_cli_with_input(&stdout, &["-i", "json", "--strict"]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(stdout, "10.0.0.0/24\n2001:db8::1/128\n");
+
+    let (stdout, _, exit_code) =
+        run_cli_with_input(input, &["-o", "yaml", "--structured", "--with-annotations"]);
+    assert_eq!(exit_code, 0);
+    assert!(stdout.contains("summary:\n  inputs: 3\n  invalid: 1\n  outputs: 2\n"));
+    assert!(stdout.contains("  annotations: []\n"));
+
+    let (stdout, _, exit_code) = run_cli_with_input(&stdout, &["-i", "yaml", "--strict"]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(stdout, "10.0.0.0/24\n2001:db8::1/128\n");
+}
+
+#[test]
+fn test_cli_stats() {
+    let input = "10.0.0.0/24\n10.0.0.1\n192.0.2.1\n192.0.2.1\ninvalid\n2001:db8::1\n";
+
+    let (stdout, stderr, exit_code) = run_cli_with_input(input, &["--stats"]);
+    assert_eq!(
+        exit_code, 0,
+        "CLI should exit successfully. stderr: {stderr}"
+    );
+    assert_eq!(stdout, "10.0.0.0/24\n192.0.2.1/32\n2001:db8::1/128\n");
+    assert!(
+        stderr.contains(
+            "\nipv4: 4 inputs, 1 duplicates, 1 covered, 2 outputs (257 addresses), build "
+        )
+    );
+    assert!(
+        stderr
+            .contains("\nipv6: 1 inputs, 0 duplicates, 0 covered, 1 outputs (1 addresses), build ")
+    );
+    assert!(stderr.ends_with("\ninvalid lines: 1\n"));
+
+    let (_, stderr, exit_code) = run_cli_with_input(input, &["--stats=json"]);
+    assert_eq!(exit_code, 0);
+    let stats: serde_json::Value = serde_json::from_str(stderr.lines().last().unwrap()).unwrap();
+    assert_eq!(stats["invalid"], 1);
+    assert_eq!(stats["ipv4"]["inputs"], 4);
+    assert_eq!(stats["ipv4"]["duplicates"], 1);
+    assert_eq!(stats["ipv4"]["covered"], 1);
+    assert_eq!(stats["ipv4"]["outputs"], 2);
+    assert_eq!(stats["ipv4"]["addresses"], "257");
+    assert!(stats["ipv6"]["build_ms"].is_f64());
+}